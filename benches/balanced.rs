@@ -0,0 +1,159 @@
+//! Memory footprint and timings of the balanced trees on a million random
+//! keys. Run with `cargo bench --bench balanced`; a counting allocator
+//! reports the live heap bytes per key once every key is inserted, and
+//! `Bst` and `std::collections::BTreeSet` are measured alongside as
+//! reference points.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeSet,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+mod common;
+
+use common::{time, Lcg};
+use data_structures::binary_trees::{Avl, Bst, RbTree, ScapegoatTree, WeightBalancedTree};
+
+const N: usize = 1_000_000;
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// The operations every benchmarked set shares, under one set of names.
+trait Set {
+    const NAME: &'static str;
+
+    fn new() -> Self;
+    fn insert(&mut self, key: u64);
+    fn contains(&self, key: u64) -> bool;
+    fn remove(&mut self, key: u64);
+}
+
+macro_rules! impl_set {
+    ($ty:ty, $name:literal) => {
+        impl Set for $ty {
+            const NAME: &'static str = $name;
+
+            fn new() -> Self {
+                <$ty>::new()
+            }
+
+            fn insert(&mut self, key: u64) {
+                self.insert(key);
+            }
+
+            fn contains(&self, key: u64) -> bool {
+                self.search(key)
+            }
+
+            fn remove(&mut self, key: u64) {
+                self.delete(key);
+            }
+        }
+    };
+}
+
+impl_set!(Bst<u64>, "bst");
+impl_set!(Avl<u64>, "avl");
+impl_set!(ScapegoatTree<u64>, "scapegoat");
+impl_set!(WeightBalancedTree<u64>, "weight-balanced");
+
+impl Set for RbTree<u64, ()> {
+    const NAME: &'static str = "rb";
+
+    fn new() -> Self {
+        RbTree::new()
+    }
+
+    fn insert(&mut self, key: u64) {
+        self.insert(key, ());
+    }
+
+    fn contains(&self, key: u64) -> bool {
+        self.contains_key(&key)
+    }
+
+    fn remove(&mut self, key: u64) {
+        self.remove(&key);
+    }
+}
+
+impl Set for BTreeSet<u64> {
+    const NAME: &'static str = "btreeset";
+
+    fn new() -> Self {
+        BTreeSet::new()
+    }
+
+    fn insert(&mut self, key: u64) {
+        self.insert(key);
+    }
+
+    fn contains(&self, key: u64) -> bool {
+        self.contains(&key)
+    }
+
+    fn remove(&mut self, key: u64) {
+        self.remove(&key);
+    }
+}
+
+fn run<S: Set>(keys: &[u64]) {
+    let name = S::NAME;
+    let before = LIVE.load(Ordering::Relaxed);
+    let mut set = time(&format!("{name} insert"), || {
+        let mut set = S::new();
+        for &k in keys {
+            set.insert(k);
+        }
+        set
+    });
+    let bytes = LIVE.load(Ordering::Relaxed) - before;
+    println!(
+        "{:<24}{:>10.1} B",
+        format!("{name} bytes/key"),
+        bytes as f64 / keys.len() as f64
+    );
+
+    time(&format!("{name} search"), || {
+        for &k in keys {
+            black_box(set.contains(k));
+        }
+    });
+    time(&format!("{name} delete"), || {
+        for &k in keys {
+            set.remove(k);
+        }
+    });
+    println!();
+}
+
+fn main() {
+    let mut rng = Lcg(0x5eed);
+    let keys: Vec<u64> = (0..N).map(|_| rng.next()).collect();
+
+    run::<Bst<u64>>(&keys);
+    run::<Avl<u64>>(&keys);
+    run::<RbTree<u64, ()>>(&keys);
+    run::<ScapegoatTree<u64>>(&keys);
+    run::<WeightBalancedTree<u64>>(&keys);
+    run::<BTreeSet<u64>>(&keys);
+}
@@ -0,0 +1,54 @@
+//! Timings for `Bst` on a million random keys. Run with
+//! `cargo bench --bench bst`; `std::collections::BTreeSet` is measured
+//! alongside as a reference point.
+
+use std::{collections::BTreeSet, hint::black_box};
+
+mod common;
+
+use common::{time, Lcg};
+use data_structures::binary_trees::Bst;
+
+const N: usize = 1_000_000;
+
+fn main() {
+    let mut rng = Lcg(0x5eed);
+    let keys: Vec<u64> = (0..N).map(|_| rng.next()).collect();
+
+    let mut tree = time("bst insert", || {
+        let mut tree = Bst::new();
+        for &k in &keys {
+            tree.insert(k);
+        }
+        tree
+    });
+    time("bst search", || {
+        for &k in &keys {
+            black_box(tree.search(k));
+        }
+    });
+    time("bst delete", || {
+        for &k in &keys {
+            tree.delete(k);
+        }
+    });
+    assert!(tree.is_empty());
+
+    let mut set = time("btreeset insert", || {
+        let mut set = BTreeSet::new();
+        for &k in &keys {
+            set.insert(k);
+        }
+        set
+    });
+    time("btreeset search", || {
+        for &k in &keys {
+            black_box(set.contains(&k));
+        }
+    });
+    time("btreeset delete", || {
+        for &k in &keys {
+            set.remove(&k);
+        }
+    });
+}
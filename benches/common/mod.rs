@@ -0,0 +1,21 @@
+//! Helpers shared by the benchmarks.
+
+use std::time::Instant;
+
+// The unit tests' generator, so the benchmarks need no copy of their own.
+#[allow(dead_code)]
+#[path = "../../src/test_util.rs"]
+mod test_util;
+
+pub(crate) use test_util::Lcg;
+
+/// Runs `f` and prints how long it took under `label`.
+pub fn time<R>(label: &str, f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    println!(
+        "{label:<24}{:>10.1} ms",
+        start.elapsed().as_secs_f64() * 1e3
+    );
+    result
+}
@@ -1,4 +1,19 @@
-#[derive(Debug)]
+use std::{
+    fmt,
+    iter::FusedIterator,
+    ops::{
+        BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, RangeBounds, Shl,
+        ShlAssign, Shr, ShrAssign, Sub, SubAssign,
+    },
+};
+
+use crate::util::index_bounds;
+
+/// A growable array of bits packed into `u64` words.
+///
+/// The padding bits past `len` in the last word are always clear, so the
+/// derived comparisons and hashing only see the logical contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BitArray {
     bits: Vec<u64>,
     size: usize,
@@ -6,13 +21,36 @@ pub struct BitArray {
 
 impl BitArray {
     pub fn new(size: usize) -> Self {
-        let elem_num = (size + 63) / 64;
+        let elem_num = size.div_ceil(64);
         Self {
             bits: vec![0; elem_num],
             size,
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The `i`-th backing word, least significant bit first.
+    pub(crate) fn word(&self, i: usize) -> u64 {
+        self.bits[i]
+    }
+
+    // The bits of word `i` that lie inside the array.
+    fn valid_mask(&self, i: usize) -> u64 {
+        let valid = self.size - i * 64;
+        if valid < 64 {
+            (1 << valid) - 1
+        } else {
+            !0
+        }
+    }
+
     #[inline(always)]
     fn idx_to_pos(&self, idx: usize) -> (usize, usize) {
         if idx >= self.size {
@@ -42,63 +80,327 @@ impl BitArray {
         self.bits[block] ^= 1 << offset;
     }
 
+    /// Sets every bit in `range`, a word at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past `len`.
+    pub fn set_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.apply_range(range, |word, mask| *word |= mask);
+    }
+
+    pub fn clear_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.apply_range(range, |word, mask| *word &= !mask);
+    }
+
+    pub fn toggle_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.apply_range(range, |word, mask| *word ^= mask);
+    }
+
+    // Calls `op` on each word overlapping `range` with a mask of the bits
+    // of that word inside it.
+    fn apply_range<R: RangeBounds<usize>>(&mut self, range: R, op: impl Fn(&mut u64, u64)) {
+        let (start, end) = index_bounds(range, self.size);
+        if start == end {
+            return;
+        }
+
+        let (first, last) = (start / 64, (end - 1) / 64);
+        for w in first..=last {
+            let lo = if w == first { start % 64 } else { 0 };
+            let hi = if w == last { (end - 1) % 64 + 1 } else { 64 };
+            op(&mut self.bits[w], (!0 >> (64 - (hi - lo))) << lo);
+        }
+    }
+
+    /// Appends a bit, growing the array by one.
+    pub fn push(&mut self, bit: bool) {
+        if self.size.is_multiple_of(64) {
+            self.bits.push(0);
+        }
+        self.size += 1;
+        if bit {
+            self.set(self.size - 1);
+        }
+    }
+
+    /// Removes and returns the last bit.
+    pub fn pop(&mut self) -> Option<bool> {
+        let last = self.size.checked_sub(1)?;
+        let bit = self.get(last);
+        self.truncate(last);
+        Some(bit)
+    }
+
+    /// Shortens the array to `len` bits. Does nothing if it is already no
+    /// longer than that.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.size {
+            self.size = len;
+            self.bits.truncate(len.div_ceil(64));
+            self.clear_tail();
+        }
+    }
+
+    /// Grows or shrinks the array to `len` bits, filling any new bits with
+    /// `value`.
+    pub fn resize(&mut self, len: usize, value: bool) {
+        let old = self.size;
+        if len <= old {
+            self.truncate(len);
+            return;
+        }
+
+        self.bits.resize(len.div_ceil(64), 0);
+        self.size = len;
+        if value {
+            self.set_range(old..);
+        }
+    }
+
+    /// Appends every bit of `other`, shifting whole words into place.
+    pub fn extend_from_bitslice(&mut self, other: &BitArray) {
+        let offset = self.size % 64;
+        let mut remaining = other.size;
+        for &word in &other.bits {
+            let count = remaining.min(64);
+            if offset == 0 {
+                self.bits.push(word);
+            } else {
+                *self.bits.last_mut().unwrap() |= word << offset;
+                if offset + count > 64 {
+                    self.bits.push(word >> (64 - offset));
+                }
+            }
+            remaining -= count;
+        }
+        self.size += other.size;
+    }
+
     pub fn invert(&mut self) {
         for block in &mut self.bits {
             *block = !*block;
         }
+        self.clear_tail();
     }
 
-    pub fn union(&self, other: &Self) -> Self {
-        assert_eq!(self.size, other.size);
-        let mut bits = Vec::with_capacity(self.bits.len());
-        for i in 0..self.bits.len() {
-            bits.push(self.bits[i] | other.bits[i]);
+    /// Rotates in place so that bit `n` becomes bit 0, like
+    /// [`slice::rotate_left`]: bits move towards the start of the array and
+    /// those that fall off the start wrap around to the end.
+    pub fn rotate_left(&mut self, n: usize) {
+        if self.size == 0 || n.is_multiple_of(self.size) {
+            return;
         }
+        let n = n % self.size;
+        let wrapped = self.clone() << (self.size - n);
+        *self >>= n;
+        self.union_with(&wrapped);
+    }
 
-        Self {
-            bits,
-            size: self.size,
+    /// Rotates in place so that bit 0 becomes bit `n`, like
+    /// [`slice::rotate_right`]: bits move towards the end of the array and
+    /// those that fall off the end wrap around to the start.
+    pub fn rotate_right(&mut self, n: usize) {
+        if self.size > 0 {
+            self.rotate_left(self.size - n % self.size);
         }
     }
 
-    pub fn intersection(&self, other: &Self) -> Self {
-        assert_eq!(self.size, other.size);
-        let mut bits = Vec::with_capacity(self.bits.len());
-        for i in 0..self.bits.len() {
-            bits.push(self.bits[i] & other.bits[i]);
+    // Moves every bit `n` positions towards the end, dropping the bits
+    // pushed past `len` and clearing the first `n`.
+    fn shift_up(&mut self, n: usize) {
+        if n >= self.size {
+            self.bits.fill(0);
+            return;
         }
 
-        Self {
-            bits,
-            size: self.size,
+        let (words, offset) = (n / 64, n % 64);
+        for i in (words..self.bits.len()).rev() {
+            let mut word = self.bits[i - words] << offset;
+            if offset > 0 && i > words {
+                word |= self.bits[i - words - 1] >> (64 - offset);
+            }
+            self.bits[i] = word;
         }
+        self.bits[..words].fill(0);
+        self.clear_tail();
     }
 
-    pub fn difference(&self, other: &Self) -> Self {
-        assert_eq!(self.size, other.size);
-        let mut bits = Vec::with_capacity(self.bits.len());
-        for i in 0..self.bits.len() {
-            bits.push(self.bits[i] & !other.bits[i]);
+    // Moves every bit `n` positions towards the start, dropping the first
+    // `n` and clearing the last `n`.
+    fn shift_down(&mut self, n: usize) {
+        if n >= self.size {
+            self.bits.fill(0);
+            return;
         }
 
-        Self {
-            bits,
-            size: self.size,
+        let (words, offset) = (n / 64, n % 64);
+        let len = self.bits.len();
+        for i in 0..len - words {
+            let mut word = self.bits[i + words] >> offset;
+            if offset > 0 && i + words + 1 < len {
+                word |= self.bits[i + words + 1] << (64 - offset);
+            }
+            self.bits[i] = word;
         }
+        self.bits[len - words..].fill(0);
     }
 
-    pub fn complement(&self) -> Self {
-        let mut bits = Vec::with_capacity(self.bits.len());
-        for i in 0..self.bits.len() {
-            bits.push(!self.bits[i]);
+    // Restores the invariant after an operation that may have set padding
+    // bits.
+    fn clear_tail(&mut self) {
+        if let Some(last) = self.bits.last_mut() {
+            *last &= match self.size % 64 {
+                0 => !0,
+                tail => (1 << tail) - 1,
+            };
         }
+    }
 
-        Self {
-            bits,
-            size: self.size,
+    // The set operations panic if the arrays differ in size; each has a
+    // `try_` counterpart that returns a `SizeMismatch` instead.
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.try_union(other).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.try_intersection(other)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.try_difference(other)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.try_symmetric_difference(other)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_union(&self, other: &Self) -> Result<Self, SizeMismatch> {
+        let mut result = self.clone();
+        result.try_union_with(other)?;
+        Ok(result)
+    }
+
+    pub fn try_intersection(&self, other: &Self) -> Result<Self, SizeMismatch> {
+        let mut result = self.clone();
+        result.try_intersect_with(other)?;
+        Ok(result)
+    }
+
+    pub fn try_difference(&self, other: &Self) -> Result<Self, SizeMismatch> {
+        let mut result = self.clone();
+        result.try_difference_with(other)?;
+        Ok(result)
+    }
+
+    pub fn try_symmetric_difference(&self, other: &Self) -> Result<Self, SizeMismatch> {
+        let mut result = self.clone();
+        result.try_symmetric_difference_with(other)?;
+        Ok(result)
+    }
+
+    /// Sets every bit that is set in `other`.
+    pub fn union_with(&mut self, other: &Self) {
+        self.try_union_with(other)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Clears every bit that is clear in `other`.
+    pub fn intersect_with(&mut self, other: &Self) {
+        self.try_intersect_with(other)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Clears every bit that is set in `other`.
+    pub fn difference_with(&mut self, other: &Self) {
+        self.try_difference_with(other)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Toggles every bit that is set in `other`.
+    pub fn symmetric_difference_with(&mut self, other: &Self) {
+        self.try_symmetric_difference_with(other)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    pub fn try_union_with(&mut self, other: &Self) -> Result<(), SizeMismatch> {
+        self.combine(other, |a, b| a | b)
+    }
+
+    pub fn try_intersect_with(&mut self, other: &Self) -> Result<(), SizeMismatch> {
+        self.combine(other, |a, b| a & b)
+    }
+
+    pub fn try_difference_with(&mut self, other: &Self) -> Result<(), SizeMismatch> {
+        self.combine(other, |a, b| a & !b)
+    }
+
+    pub fn try_symmetric_difference_with(&mut self, other: &Self) -> Result<(), SizeMismatch> {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    /// Whether every bit set in `self` is also set in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.try_is_subset(other)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Whether every bit set in `other` is also set in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Whether no bit is set in both arrays.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.try_is_disjoint(other)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_is_subset(&self, other: &Self) -> Result<bool, SizeMismatch> {
+        self.check_size(other)?;
+        Ok(self.bits.iter().zip(&other.bits).all(|(a, b)| a & !b == 0))
+    }
+
+    pub fn try_is_superset(&self, other: &Self) -> Result<bool, SizeMismatch> {
+        other.try_is_subset(self)
+    }
+
+    pub fn try_is_disjoint(&self, other: &Self) -> Result<bool, SizeMismatch> {
+        self.check_size(other)?;
+        Ok(self.bits.iter().zip(&other.bits).all(|(a, b)| a & b == 0))
+    }
+
+    fn check_size(&self, other: &Self) -> Result<(), SizeMismatch> {
+        if self.size == other.size {
+            Ok(())
+        } else {
+            Err(SizeMismatch {
+                left: self.size,
+                right: other.size,
+            })
         }
     }
 
+    // Applies `op` word by word. None of the operations used can set a
+    // padding bit that is clear in both operands.
+    fn combine(&mut self, other: &Self, op: impl Fn(u64, u64) -> u64) -> Result<(), SizeMismatch> {
+        self.check_size(other)?;
+        for (a, &b) in self.bits.iter_mut().zip(&other.bits) {
+            *a = op(*a, b);
+        }
+        Ok(())
+    }
+
+    pub fn complement(&self) -> Self {
+        let mut complement = self.clone();
+        complement.invert();
+        complement
+    }
+
     pub fn iter_bits<F>(&self, mut f: F)
     where
         F: FnMut(usize, bool),
@@ -119,17 +421,315 @@ impl BitArray {
             }
         }
     }
+
+    /// Positions of the set bits in ascending order, skipping whole words
+    /// of zeros at a time.
+    pub fn iter_ones(&self) -> BitPositions<'_> {
+        BitPositions::new(self, 0)
+    }
+
+    /// Positions of the clear bits in ascending order.
+    pub fn iter_zeros(&self) -> BitPositions<'_> {
+        BitPositions::new(self, !0)
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.size - self.count_ones()
+    }
+
+    pub fn first_one(&self) -> Option<usize> {
+        self.iter_ones().next()
+    }
+
+    pub fn last_one(&self) -> Option<usize> {
+        let (i, word) = self.bits.iter().enumerate().rfind(|(_, &w)| w != 0)?;
+        Some(i * 64 + 63 - word.leading_zeros() as usize)
+    }
+
+    /// First set bit strictly after position `idx`.
+    pub fn next_one_after(&self, idx: usize) -> Option<usize> {
+        BitPositions::starting_after(self, 0, idx).next()
+    }
+
+    /// First clear bit strictly after position `idx`.
+    pub fn next_zero_after(&self, idx: usize) -> Option<usize> {
+        BitPositions::starting_after(self, !0, idx).next()
+    }
+
+    /// Whether any bit is set.
+    pub fn any(&self) -> bool {
+        self.bits.iter().any(|&w| w != 0)
+    }
+
+    /// Whether every bit is set. True for an empty array.
+    pub fn all(&self) -> bool {
+        self.count_ones() == self.size
+    }
+
+    /// Whether no bit is set.
+    pub fn none(&self) -> bool {
+        !self.any()
+    }
+}
+
+/// Iterator over the positions of the set or clear bits of a `BitArray`,
+/// returned by [`BitArray::iter_ones`] and [`BitArray::iter_zeros`].
+pub struct BitPositions<'a> {
+    array: &'a BitArray,
+    // XORed into each word, so that the wanted bits read as ones.
+    flip: u64,
+    // Index of the word `current` came from.
+    index: usize,
+    // The wanted bits of that word not yet returned.
+    current: u64,
+}
+
+impl<'a> BitPositions<'a> {
+    fn new(array: &'a BitArray, flip: u64) -> Self {
+        let current = if array.bits.is_empty() {
+            0
+        } else {
+            (array.bits[0] ^ flip) & array.valid_mask(0)
+        };
+        Self {
+            array,
+            flip,
+            index: 0,
+            current,
+        }
+    }
+
+    fn starting_after(array: &'a BitArray, flip: u64, idx: usize) -> Self {
+        let start = idx.saturating_add(1);
+        if start >= array.size {
+            return Self {
+                array,
+                flip,
+                index: array.bits.len(),
+                current: 0,
+            };
+        }
+
+        let index = start / 64;
+        let current = (array.bits[index] ^ flip) & array.valid_mask(index) & (!0 << (start % 64));
+        Self {
+            array,
+            flip,
+            index,
+            current,
+        }
+    }
+}
+
+impl Iterator for BitPositions<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            if self.index + 1 >= self.array.bits.len() {
+                self.index = self.array.bits.len();
+                return None;
+            }
+            self.index += 1;
+            self.current =
+                (self.array.bits[self.index] ^ self.flip) & self.array.valid_mask(self.index);
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        Some(self.index * 64 + bit)
+    }
+}
+
+impl FusedIterator for BitPositions<'_> {}
+
+/// The error returned by the `try_` set operations when the two arrays
+/// differ in size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub left: usize,
+    pub right: usize,
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bit arrays differ in size: {} and {}",
+            self.left, self.right
+        )
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+// The operators panic on a size mismatch, like the named methods.
+macro_rules! impl_set_op {
+    ($op:ident, $method:ident, $assign:ident, $assign_method:ident, $with:ident) => {
+        impl $op<&BitArray> for &BitArray {
+            type Output = BitArray;
+
+            fn $method(self, rhs: &BitArray) -> BitArray {
+                let mut result = self.clone();
+                result.$with(rhs);
+                result
+            }
+        }
+
+        impl $op<&BitArray> for BitArray {
+            type Output = BitArray;
+
+            fn $method(mut self, rhs: &BitArray) -> BitArray {
+                self.$with(rhs);
+                self
+            }
+        }
+
+        impl $op<BitArray> for BitArray {
+            type Output = BitArray;
+
+            fn $method(mut self, rhs: BitArray) -> BitArray {
+                self.$with(&rhs);
+                self
+            }
+        }
+
+        impl $assign<&BitArray> for BitArray {
+            fn $assign_method(&mut self, rhs: &BitArray) {
+                self.$with(rhs);
+            }
+        }
+
+        impl $assign<BitArray> for BitArray {
+            fn $assign_method(&mut self, rhs: BitArray) {
+                self.$with(&rhs);
+            }
+        }
+    };
+}
+
+impl_set_op!(BitOr, bitor, BitOrAssign, bitor_assign, union_with);
+impl_set_op!(BitAnd, bitand, BitAndAssign, bitand_assign, intersect_with);
+impl_set_op!(
+    BitXor,
+    bitxor,
+    BitXorAssign,
+    bitxor_assign,
+    symmetric_difference_with
+);
+impl_set_op!(Sub, sub, SubAssign, sub_assign, difference_with);
+
+// Shifts count positions in bit order, so `<<` moves bits towards the end
+// of the array just as it moves them towards the high end of an integer.
+impl Shl<usize> for BitArray {
+    type Output = BitArray;
+
+    fn shl(mut self, n: usize) -> BitArray {
+        self.shift_up(n);
+        self
+    }
+}
+
+impl Shr<usize> for BitArray {
+    type Output = BitArray;
+
+    fn shr(mut self, n: usize) -> BitArray {
+        self.shift_down(n);
+        self
+    }
+}
+
+impl ShlAssign<usize> for BitArray {
+    fn shl_assign(&mut self, n: usize) {
+        self.shift_up(n);
+    }
+}
+
+impl ShrAssign<usize> for BitArray {
+    fn shr_assign(&mut self, n: usize) {
+        self.shift_down(n);
+    }
+}
+
+impl FromIterator<bool> for BitArray {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bit_array = BitArray::new(0);
+        bit_array.extend(iter);
+        bit_array
+    }
+}
+
+impl Extend<bool> for BitArray {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+impl Not for &BitArray {
+    type Output = BitArray;
+
+    fn not(self) -> BitArray {
+        self.complement()
+    }
+}
+
+impl Not for BitArray {
+    type Output = BitArray;
+
+    fn not(mut self) -> BitArray {
+        self.invert();
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::BitArray;
+
+    // Serialized as `(len, words)`.
+    impl Serialize for BitArray {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            (self.size, &self.bits).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for BitArray {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (size, bits): (usize, Vec<u64>) = Deserialize::deserialize(deserializer)?;
+            if bits.len() != size.div_ceil(64) {
+                return Err(de::Error::invalid_length(
+                    bits.len(),
+                    &"one 64-bit word per 64 bits",
+                ));
+            }
+            if let (Some(last), tail @ 1..) = (bits.last(), size % 64) {
+                if last >> tail != 0 {
+                    return Err(de::Error::custom("bits set past the end of the array"));
+                }
+            }
+            Ok(Self { bits, size })
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::Lcg;
 
     #[test]
     fn test_new() {
         let bit_array = BitArray::new(100);
         assert_eq!(bit_array.size, 100);
-        assert_eq!(bit_array.bits.len(), (100 + 63) / 64);
+        assert_eq!(bit_array.bits.len(), 100_usize.div_ceil(64));
     }
 
     #[test]
@@ -248,4 +848,481 @@ mod tests {
         bit_array.clear(127);
         assert!(!bit_array.get(127));
     }
+
+    #[test]
+    fn test_iter_ones_and_zeros() {
+        let mut bit_array = BitArray::new(130);
+        for i in [0, 63, 64, 129] {
+            bit_array.set(i);
+        }
+        assert!(bit_array.iter_ones().eq([0, 63, 64, 129]));
+        assert_eq!(bit_array.iter_zeros().count(), 126);
+        assert!(bit_array.iter_zeros().take(3).eq([1, 2, 3]));
+        assert_eq!(bit_array.iter_zeros().last(), Some(128));
+
+        let empty = BitArray::new(0);
+        assert_eq!(empty.iter_ones().next(), None);
+        assert_eq!(empty.iter_zeros().next(), None);
+    }
+
+    #[test]
+    fn test_sparse_large_array() {
+        let mut bit_array = BitArray::new(1_000_000);
+        for i in [5, 500_000, 999_999] {
+            bit_array.set(i);
+        }
+        assert!(bit_array.iter_ones().eq([5, 500_000, 999_999]));
+        assert_eq!(bit_array.count_ones(), 3);
+        assert_eq!(bit_array.count_zeros(), 999_997);
+        assert_eq!(bit_array.first_one(), Some(5));
+        assert_eq!(bit_array.last_one(), Some(999_999));
+        assert_eq!(bit_array.next_one_after(5), Some(500_000));
+        assert_eq!(bit_array.next_one_after(500_000), Some(999_999));
+        assert_eq!(bit_array.next_one_after(999_999), None);
+        assert_eq!(bit_array.next_one_after(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_next_zero_after() {
+        let mut bit_array = BitArray::new(200);
+        for i in 0..150 {
+            bit_array.set(i);
+        }
+        assert_eq!(bit_array.next_zero_after(0), Some(150));
+        assert_eq!(bit_array.next_zero_after(160), Some(161));
+        assert_eq!(bit_array.next_zero_after(199), None);
+        assert_eq!(bit_array.next_one_after(148), Some(149));
+        assert_eq!(bit_array.next_one_after(149), None);
+    }
+
+    #[test]
+    fn test_queries_mask_tail_bits() {
+        let mut bit_array = BitArray::new(70);
+        bit_array.invert();
+        assert!(bit_array.all());
+        assert_eq!(bit_array.count_ones(), 70);
+        assert_eq!(bit_array.last_one(), Some(69));
+        assert_eq!(bit_array.iter_ones().last(), Some(69));
+        assert_eq!(bit_array.next_one_after(69), None);
+
+        bit_array.clear(3);
+        assert!(!bit_array.all());
+        assert!(bit_array.iter_zeros().eq([3]));
+        assert_eq!(bit_array.next_zero_after(3), None);
+
+        let mut bit_array = BitArray::new(70);
+        assert!(bit_array.none());
+        assert!(!bit_array.any());
+        assert_eq!(bit_array.first_one(), None);
+        assert_eq!(bit_array.last_one(), None);
+        bit_array.set(66);
+        assert!(bit_array.any());
+        assert!(!bit_array.none());
+
+        assert!(BitArray::new(0).all());
+        assert!(BitArray::new(0).none());
+    }
+
+    #[test]
+    fn test_search_against_naive() {
+        let mut rng = Lcg(22);
+        let mut bit_array = BitArray::new(300);
+        for i in 0..300 {
+            if rng.next().is_multiple_of(3) {
+                bit_array.set(i);
+            }
+        }
+
+        let bits: Vec<bool> = (0..300).map(|i| bit_array.get(i)).collect();
+        let ones: Vec<usize> = (0..300).filter(|&i| bits[i]).collect();
+        let zeros: Vec<usize> = (0..300).filter(|&i| !bits[i]).collect();
+        assert!(bit_array.iter_ones().eq(ones.iter().copied()));
+        assert!(bit_array.iter_zeros().eq(zeros.iter().copied()));
+        for i in 0..300 {
+            assert_eq!(
+                bit_array.next_one_after(i),
+                ones.iter().copied().find(|&j| j > i)
+            );
+            assert_eq!(
+                bit_array.next_zero_after(i),
+                zeros.iter().copied().find(|&j| j > i)
+            );
+        }
+    }
+
+    fn from_model(model: &[bool]) -> BitArray {
+        let mut bit_array = BitArray::new(model.len());
+        for (i, _) in model.iter().enumerate().filter(|(_, &bit)| bit) {
+            bit_array.set(i);
+        }
+        bit_array
+    }
+
+    fn hash_of(bit_array: &BitArray) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        bit_array.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn check_against_model(bit_array: &BitArray, model: &[bool]) {
+        if let (Some(last), tail @ 1..) = (bit_array.bits.last(), model.len() % 64) {
+            assert_eq!(last >> tail, 0, "padding bits must stay clear");
+        }
+        assert_eq!(bit_array.len(), model.len());
+        assert!((0..model.len()).all(|i| bit_array.get(i) == model[i]));
+
+        let ones: Vec<usize> = (0..model.len()).filter(|&i| model[i]).collect();
+        assert_eq!(bit_array.count_ones(), ones.len());
+        assert!(bit_array.iter_ones().eq(ones.iter().copied()));
+        assert_eq!(bit_array.last_one(), ones.last().copied());
+        assert_eq!(bit_array.all(), ones.len() == model.len());
+
+        let rebuilt = from_model(model);
+        assert_eq!(*bit_array, rebuilt);
+        assert_eq!(hash_of(bit_array), hash_of(&rebuilt));
+        assert_eq!(bit_array.clone(), *bit_array);
+    }
+
+    #[test]
+    fn test_operations_against_vec_bool() {
+        let mut rng = Lcg(23);
+        for len in [0, 1, 63, 64, 65, 127, 128, 130, 200] {
+            let mut model = rng.bools(len);
+            let mut bit_array = from_model(&model);
+            for _ in 0..200 {
+                let idx = (rng.next() as usize) % len.max(1);
+                let other_model = rng.bools(len);
+                let other = from_model(&other_model);
+                match rng.next() % 9 {
+                    0 if len > 0 => {
+                        bit_array.set(idx);
+                        model[idx] = true;
+                    }
+                    1 if len > 0 => {
+                        bit_array.clear(idx);
+                        model[idx] = false;
+                    }
+                    2 if len > 0 => {
+                        bit_array.toggle(idx);
+                        model[idx] = !model[idx];
+                    }
+                    3 => {
+                        bit_array.invert();
+                        model.iter_mut().for_each(|bit| *bit = !*bit);
+                    }
+                    4 => {
+                        bit_array = bit_array.union(&other);
+                        model
+                            .iter_mut()
+                            .zip(&other_model)
+                            .for_each(|(a, &b)| *a |= b);
+                    }
+                    5 => {
+                        bit_array = bit_array.intersection(&other);
+                        model
+                            .iter_mut()
+                            .zip(&other_model)
+                            .for_each(|(a, &b)| *a &= b);
+                    }
+                    6 => {
+                        bit_array = bit_array.difference(&other);
+                        model
+                            .iter_mut()
+                            .zip(&other_model)
+                            .for_each(|(a, &b)| *a &= !b);
+                    }
+                    7 => {
+                        bit_array ^= &other;
+                        model
+                            .iter_mut()
+                            .zip(&other_model)
+                            .for_each(|(a, &b)| *a ^= b);
+                    }
+                    _ => {
+                        bit_array = bit_array.complement();
+                        model.iter_mut().for_each(|bit| *bit = !*bit);
+                    }
+                }
+                check_against_model(&bit_array, &model);
+            }
+        }
+    }
+
+    #[test]
+    fn test_equality_ignores_history() {
+        let mut a = BitArray::new(70);
+        a.invert();
+        a.invert();
+        assert_eq!(a, BitArray::new(70));
+        assert_eq!(hash_of(&a), hash_of(&BitArray::new(70)));
+
+        let full = BitArray::new(70).complement();
+        let mut b = BitArray::new(70);
+        for i in 0..70 {
+            b.set(i);
+        }
+        assert_eq!(full, b);
+        assert_eq!(full.count_zeros(), 0);
+        assert_ne!(BitArray::new(70), BitArray::new(71));
+    }
+
+    #[test]
+    fn test_in_place_operations() {
+        let (mut a, mut b) = (BitArray::new(100), BitArray::new(100));
+        for i in [1, 2, 70] {
+            a.set(i);
+        }
+        for i in [2, 3, 99] {
+            b.set(i);
+        }
+
+        let mut c = BitArray::new(100);
+        c.union_with(&a);
+        c.union_with(&b);
+        assert!(c.iter_ones().eq([1, 2, 3, 70, 99]));
+        assert_eq!(c, a.union(&b));
+
+        c.intersect_with(&a);
+        assert_eq!(c, a);
+        c.difference_with(&b);
+        assert!(c.iter_ones().eq([1, 70]));
+        c.symmetric_difference_with(&a);
+        assert!(c.iter_ones().eq([2]));
+
+        let mut d = a.clone();
+        d.symmetric_difference_with(&b);
+        assert!(d.iter_ones().eq([1, 3, 70, 99]));
+        assert_eq!(d, a.symmetric_difference(&b));
+    }
+
+    #[test]
+    fn test_operators() {
+        let (mut a, mut b) = (BitArray::new(70), BitArray::new(70));
+        a.set(0);
+        a.set(69);
+        b.set(69);
+        b.set(5);
+
+        assert!((&a | &b).iter_ones().eq([0, 5, 69]));
+        assert!((&a & &b).iter_ones().eq([69]));
+        assert!((&a ^ &b).iter_ones().eq([0, 5]));
+        assert!((&a - &b).iter_ones().eq([0]));
+        assert_eq!((!&a).count_ones(), 68);
+        assert_eq!(!!a.clone(), a);
+        assert_eq!(a.clone() | b.clone(), &a | &b);
+
+        let mut c = a.clone();
+        c |= &b;
+        c -= b.clone();
+        assert_eq!(c, &a - &b);
+        c &= &a;
+        c ^= a.clone();
+        assert!(c.iter_ones().eq([69]));
+    }
+
+    #[test]
+    fn test_subset_superset_disjoint() {
+        let (mut a, mut b) = (BitArray::new(130), BitArray::new(130));
+        a.set(3);
+        b.set(3);
+        b.set(128);
+
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(b.is_superset(&a));
+        assert!(a.is_subset(&a));
+        assert!(!a.is_disjoint(&b));
+        assert!(a.is_disjoint(&(!&b)));
+        assert!(BitArray::new(130).is_subset(&a));
+        assert!(BitArray::new(130).is_disjoint(&BitArray::new(130)));
+    }
+
+    #[test]
+    fn test_try_operations_report_size_mismatch() {
+        let mut a = BitArray::new(64);
+        let b = BitArray::new(65);
+        let err = SizeMismatch {
+            left: 64,
+            right: 65,
+        };
+
+        assert_eq!(a.try_union(&b), Err(err));
+        assert_eq!(a.try_intersection(&b), Err(err));
+        assert_eq!(a.try_difference(&b), Err(err));
+        assert_eq!(a.try_symmetric_difference(&b), Err(err));
+        assert_eq!(a.try_union_with(&b), Err(err));
+        assert_eq!(a.try_intersect_with(&b), Err(err));
+        assert_eq!(a.try_difference_with(&b), Err(err));
+        assert_eq!(a.try_symmetric_difference_with(&b), Err(err));
+        assert_eq!(a.try_is_subset(&b), Err(err));
+        assert_eq!(a.try_is_disjoint(&b), Err(err));
+        assert_eq!(
+            a.try_is_superset(&b),
+            Err(SizeMismatch {
+                left: 65,
+                right: 64
+            })
+        );
+        assert_eq!(err.to_string(), "bit arrays differ in size: 64 and 65");
+
+        assert_eq!(a.try_union(&BitArray::new(64)), Ok(BitArray::new(64)));
+        assert_eq!(a.try_is_subset(&BitArray::new(64)), Ok(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "bit arrays differ in size: 64 and 65")]
+    fn test_operator_size_mismatch_panics() {
+        let _ = BitArray::new(64) | BitArray::new(65);
+    }
+
+    #[test]
+    fn test_growth_against_vec_bool() {
+        let mut rng = Lcg(25);
+        let mut model = Vec::new();
+        let mut bit_array = BitArray::new(0);
+        for _ in 0..2000 {
+            match rng.next() % 6 {
+                0 | 1 => {
+                    let bit = rng.next() % 2 == 1;
+                    bit_array.push(bit);
+                    model.push(bit);
+                }
+                2 => assert_eq!(bit_array.pop(), model.pop()),
+                3 => {
+                    let len = (rng.next() as usize) % (model.len() + 1);
+                    bit_array.truncate(len);
+                    model.truncate(len);
+                }
+                4 => {
+                    let len = (rng.next() as usize) % 300;
+                    let value = rng.next() % 2 == 1;
+                    bit_array.resize(len, value);
+                    model.resize(len, value);
+                }
+                _ => {
+                    let len = (rng.next() as usize) % 150;
+                    let other_model = rng.bools(len);
+                    bit_array.extend_from_bitslice(&from_model(&other_model));
+                    model.extend(other_model);
+                }
+            }
+            check_against_model(&bit_array, &model);
+        }
+    }
+
+    #[test]
+    fn test_shifts_and_rotates_against_vec_bool() {
+        let mut rng = Lcg(26);
+        for len in [0, 1, 63, 64, 65, 127, 128, 130, 200] {
+            let model = rng.bools(len);
+            let bit_array = from_model(&model);
+            for n in [0, 1, 5, 63, 64, 65, 128, 129, 199, 200, 201, 500] {
+                let shifted: Vec<bool> = (0..len).map(|i| i >= n && model[i - n]).collect();
+                check_against_model(&(bit_array.clone() << n), &shifted);
+
+                let shifted: Vec<bool> = (0..len).map(|i| i + n < len && model[i + n]).collect();
+                check_against_model(&(bit_array.clone() >> n), &shifted);
+
+                let (mut left, mut right) = (model.clone(), model.clone());
+                if len > 0 {
+                    left.rotate_left(n % len);
+                    right.rotate_right(n % len);
+                }
+                let mut rotated = bit_array.clone();
+                rotated.rotate_left(n);
+                check_against_model(&rotated, &left);
+                rotated.rotate_right(n);
+                check_against_model(&rotated, &model);
+                rotated.rotate_right(n);
+                check_against_model(&rotated, &right);
+            }
+        }
+
+        let mut bit_array = BitArray::new(5);
+        bit_array.set(1);
+        bit_array.rotate_left(1);
+        assert_eq!(bit_array.first_one(), Some(0));
+        bit_array.rotate_left(1);
+        assert_eq!(bit_array.first_one(), Some(4));
+    }
+
+    #[test]
+    fn test_range_operations_against_vec_bool() {
+        let mut rng = Lcg(27);
+        for len in [0, 1, 63, 64, 65, 127, 128, 130, 200] {
+            let mut model = rng.bools(len);
+            let mut bit_array = from_model(&model);
+            for _ in 0..200 {
+                let a = (rng.next() as usize) % (len + 1);
+                let b = (rng.next() as usize) % (len + 1);
+                let (start, end) = (a.min(b), a.max(b));
+                match rng.next() % 3 {
+                    0 => {
+                        bit_array.set_range(start..end);
+                        model[start..end].fill(true);
+                    }
+                    1 => {
+                        bit_array.clear_range(start..end);
+                        model[start..end].fill(false);
+                    }
+                    _ => {
+                        bit_array.toggle_range(start..end);
+                        model[start..end].iter_mut().for_each(|bit| *bit = !*bit);
+                    }
+                }
+                check_against_model(&bit_array, &model);
+            }
+        }
+
+        let mut bit_array = BitArray::new(130);
+        bit_array.set_range(..);
+        assert!(bit_array.all());
+        bit_array.clear_range(64..=64);
+        assert_eq!(bit_array.next_zero_after(0), Some(64));
+        bit_array.toggle_range(..=1);
+        assert_eq!(bit_array.first_one(), Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "range end out of bounds")]
+    fn test_range_out_of_bounds() {
+        BitArray::new(64).set_range(60..65);
+    }
+
+    #[test]
+    fn test_from_iter_and_extend() {
+        let pattern = [true, false, true, true];
+        let mut bit_array: BitArray = pattern.iter().copied().cycle().take(100).collect();
+        bit_array.extend([false, true]);
+        let model: Vec<bool> = pattern
+            .iter()
+            .copied()
+            .cycle()
+            .take(100)
+            .chain([false, true])
+            .collect();
+        check_against_model(&bit_array, &model);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut bit_array = BitArray::new(70);
+        bit_array.set(3);
+        bit_array.set(69);
+        bit_array.invert();
+
+        let json = serde_json::to_string(&bit_array).unwrap();
+        let back: BitArray = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.size, 70);
+        for i in 0..70 {
+            assert_eq!(back.get(i), i != 3 && i != 69);
+        }
+        assert_eq!(back.bits[1] >> 6, 0);
+
+        assert!(serde_json::from_str::<BitArray>("[70,[0]]").is_err());
+        assert!(serde_json::from_str::<BitArray>("[65,[0,2]]").is_err());
+    }
 }
@@ -99,6 +99,35 @@ impl<T: Default + Copy, const N: usize> Extend<T> for CircularBuffer<T, N> {
     }
 }
 
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::CircularBuffer;
+
+    // Serialized as a sequence from the oldest element to the newest.
+    impl<T: Serialize, const N: usize> Serialize for CircularBuffer<T, N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let items = (0..self.size).map(|i| &self.buffer[(self.tail + i) % N]);
+            serializer.collect_seq(items)
+        }
+    }
+
+    impl<'de, T, const N: usize> Deserialize<'de> for CircularBuffer<T, N>
+    where
+        T: Deserialize<'de> + Default + Copy,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let items = Vec::<T>::deserialize(deserializer)?;
+            if items.len() > N {
+                let expected = format!("at most {N} elements");
+                return Err(de::Error::invalid_length(items.len(), &expected.as_str()));
+            }
+            Ok(items.into_iter().collect())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,4 +225,16 @@ mod tests {
             vec![2, 3, 4, 5]
         );
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let buffer: CircularBuffer<i32, 4> = (1..=6).collect();
+        let json = serde_json::to_string(&buffer).unwrap();
+        assert_eq!(json, "[3,4,5,6]");
+
+        let back: CircularBuffer<i32, 4> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(buffer.iter()));
+        assert!(serde_json::from_str::<CircularBuffer<i32, 2>>(&json).is_err());
+    }
 }
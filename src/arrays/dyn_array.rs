@@ -31,6 +31,14 @@ impl<T: Default + Clone + Copy, const N: usize> DynamicArray<T, N> {
         }
     }
 
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        if idx < self.len {
+            Some(&mut self.buffer[idx])
+        } else {
+            None
+        }
+    }
+
     pub fn pop(&mut self) -> Option<T> {
         if self.len == 0 {
             None
@@ -44,6 +52,10 @@ impl<T: Default + Clone + Copy, const N: usize> DynamicArray<T, N> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn capacity(&self) -> usize {
         self.capacity
     }
@@ -62,6 +74,32 @@ impl<T: Default + Clone + Copy, const N: usize> Default for DynamicArray<T, N> {
     }
 }
 
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::DynamicArray;
+
+    impl<T: Serialize, const N: usize> Serialize for DynamicArray<T, N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(&self.buffer[..self.len])
+        }
+    }
+
+    impl<'de, T, const N: usize> Deserialize<'de> for DynamicArray<T, N>
+    where
+        T: Deserialize<'de> + Default + Copy,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut array = Self::new();
+            for val in Vec::<T>::deserialize(deserializer)? {
+                array.push(val);
+            }
+            Ok(array)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,6 +136,16 @@ mod tests {
         assert_eq!(arr.get(2), None);
     }
 
+    #[test]
+    fn test_get_mut() {
+        let mut arr: DynamicArray<i32, 2> = DynamicArray::new();
+        arr.push(10);
+        *arr.get_mut(0).unwrap() += 5;
+
+        assert_eq!(arr.get(0), Some(&15));
+        assert_eq!(arr.get_mut(1), None);
+    }
+
     #[test]
     fn test_pop() {
         let mut arr: DynamicArray<i32, 5> = DynamicArray::new();
@@ -149,4 +197,19 @@ mod tests {
         assert_eq!(arr.get(0), Some(&1));
         assert_eq!(arr.get(1), None);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut arr: DynamicArray<i32, 2> = DynamicArray::new();
+        for i in 0..5 {
+            arr.push(i * 10);
+        }
+        let json = serde_json::to_string(&arr).unwrap();
+        assert_eq!(json, "[0,10,20,30,40]");
+
+        let back: DynamicArray<i32, 2> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 5);
+        assert!((0..5).all(|i| back.get(i) == arr.get(i)));
+    }
 }
@@ -106,7 +106,10 @@ impl GapBuffer {
         let mut new_buffer = Vec::with_capacity(new_size);
 
         new_buffer.extend(self.buffer.iter().take(self.cursor).cloned());
-        new_buffer.extend(std::iter::repeat('\0').take(new_size.saturating_sub(used_size)));
+        new_buffer.extend(std::iter::repeat_n(
+            '\0',
+            new_size.saturating_sub(used_size),
+        ));
         new_buffer.extend(self.buffer.iter().skip(self.gap_end).cloned());
 
         self.buffer = new_buffer;
@@ -127,6 +130,32 @@ impl GapBuffer {
     }
 }
 
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::GapBuffer;
+
+    // Serialized as its text; the cursor and gap are not kept, and a
+    // deserialized buffer has its cursor at the end.
+    impl Serialize for GapBuffer {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.extract_text())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for GapBuffer {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let text = String::deserialize(deserializer)?;
+            let mut buffer = GapBuffer::new(text.chars().count());
+            for c in text.chars() {
+                buffer.insert(c);
+            }
+            Ok(buffer)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,4 +296,22 @@ mod tests {
         gap_buffer.move_cursor(usize::MAX);
         assert_eq!(gap_buffer.cursor, 5);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut gap_buffer = GapBuffer::new(4);
+        for c in "hello".chars() {
+            gap_buffer.insert(c);
+        }
+        gap_buffer.move_cursor(2);
+
+        let json = serde_json::to_string(&gap_buffer).unwrap();
+        assert_eq!(json, "\"hello\"");
+
+        let mut back: GapBuffer = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.extract_text(), "hello");
+        back.insert('!');
+        assert_eq!(back.extract_text(), "hello!");
+    }
 }
@@ -1,4 +1,13 @@
-mod bit_array;
-mod circular_buffer;
-mod dyn_array;
-mod gap_buffer;
+//! Array-backed containers.
+
+pub mod bit_array;
+pub mod circular_buffer;
+pub mod dyn_array;
+pub mod gap_buffer;
+pub mod rank_select;
+
+pub use bit_array::{BitArray, BitPositions, SizeMismatch};
+pub use circular_buffer::CircularBuffer;
+pub use dyn_array::DynamicArray;
+pub use gap_buffer::GapBuffer;
+pub use rank_select::RankSelect;
@@ -0,0 +1,295 @@
+use super::BitArray;
+
+// Bits per superblock and per block. Superblocks store absolute counts and
+// blocks store counts relative to their superblock, which costs about 6% of
+// the bit array on top of it.
+const SUPERBLOCK_BITS: usize = 2048;
+const BLOCK_BITS: usize = 512;
+const WORDS_PER_BLOCK: usize = BLOCK_BITS / 64;
+const BLOCKS_PER_SUPERBLOCK: usize = SUPERBLOCK_BITS / BLOCK_BITS;
+
+/// A read-only `BitArray` with a rank directory: `rank1` and `rank0` run in
+/// constant time and `select1` and `select0` in `O(log n)`.
+#[derive(Debug)]
+pub struct RankSelect {
+    bits: BitArray,
+    ones: usize,
+    // Ones before the start of each superblock.
+    superblocks: Vec<usize>,
+    // Ones between the start of each block's superblock and the block.
+    blocks: Vec<u16>,
+}
+
+impl RankSelect {
+    pub fn new(bits: BitArray) -> Self {
+        let mut rank = Self {
+            bits,
+            ones: 0,
+            superblocks: Vec::new(),
+            blocks: Vec::new(),
+        };
+
+        let mut ones = 0;
+        let mut relative = 0;
+        for i in 0..rank.len().div_ceil(64) {
+            if i % (SUPERBLOCK_BITS / 64) == 0 {
+                rank.superblocks.push(ones);
+                relative = 0;
+            }
+            if i % WORDS_PER_BLOCK == 0 {
+                rank.blocks.push(relative as u16);
+            }
+            let count = rank.word(i).count_ones() as usize;
+            ones += count;
+            relative += count;
+        }
+        rank.ones = ones;
+        rank
+    }
+
+    pub fn bits(&self) -> &BitArray {
+        &self.bits
+    }
+
+    pub fn into_inner(self) -> BitArray {
+        self.bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len() - self.ones
+    }
+
+    /// Number of ones before position `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i > len`.
+    pub fn rank1(&self, i: usize) -> usize {
+        assert!(i <= self.len(), "index out of bounds");
+        if i == self.len() {
+            return self.ones;
+        }
+
+        let word = i / 64;
+        let block = word / WORDS_PER_BLOCK;
+        let mut rank =
+            self.superblocks[word / (SUPERBLOCK_BITS / 64)] + self.blocks[block] as usize;
+        for w in block * WORDS_PER_BLOCK..word {
+            rank += self.word(w).count_ones() as usize;
+        }
+        rank + (self.word(word) & ((1 << (i % 64)) - 1)).count_ones() as usize
+    }
+
+    /// Number of zeros before position `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i > len`.
+    pub fn rank0(&self, i: usize) -> usize {
+        i - self.rank1(i)
+    }
+
+    /// Position of the `k`-th one, counting from zero.
+    pub fn select1(&self, k: usize) -> Option<usize> {
+        if k >= self.ones {
+            return None;
+        }
+        Some(self.select(
+            k,
+            |w| self.word(w),
+            |s| self.superblocks[s],
+            |b| self.blocks[b] as usize,
+        ))
+    }
+
+    /// Position of the `k`-th zero, counting from zero.
+    pub fn select0(&self, k: usize) -> Option<usize> {
+        if k >= self.count_zeros() {
+            return None;
+        }
+        let superblock = |s: usize| s * SUPERBLOCK_BITS - self.superblocks[s];
+        let block = |b: usize| (b % BLOCKS_PER_SUPERBLOCK) * BLOCK_BITS - self.blocks[b] as usize;
+        Some(self.select(k, |w| !self.word(w), superblock, block))
+    }
+
+    /// Finds the `k`-th set bit of `word`, given the counts of set bits
+    /// before each superblock and before each block within its superblock.
+    fn select(
+        &self,
+        mut k: usize,
+        word: impl Fn(usize) -> u64,
+        superblock: impl Fn(usize) -> usize,
+        block: impl Fn(usize) -> usize,
+    ) -> usize {
+        let s = partition_point(self.superblocks.len(), |s| superblock(s) <= k) - 1;
+        k -= superblock(s);
+
+        let first = s * BLOCKS_PER_SUPERBLOCK;
+        let end = self.blocks.len().min(first + BLOCKS_PER_SUPERBLOCK);
+        let b = first + partition_point(end - first, |b| block(first + b) <= k) - 1;
+        k -= block(b);
+
+        let mut w = b * WORDS_PER_BLOCK;
+        loop {
+            let mut bits = word(w);
+            let count = bits.count_ones() as usize;
+            if k < count {
+                for _ in 0..k {
+                    bits &= bits - 1;
+                }
+                return w * 64 + bits.trailing_zeros() as usize;
+            }
+            k -= count;
+            w += 1;
+        }
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self.bits.word(i)
+    }
+}
+
+impl From<BitArray> for RankSelect {
+    fn from(bits: BitArray) -> Self {
+        Self::new(bits)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{BitArray, RankSelect};
+
+    // Only the bits are serialized; the directory is rebuilt on load.
+    impl Serialize for RankSelect {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.bits.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RankSelect {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            BitArray::deserialize(deserializer).map(Self::new)
+        }
+    }
+}
+
+/// Returns the first index in `0..len` for which `pred` is false, given that
+/// `pred` holds for a prefix of the range.
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    fn check_against_naive(rank: &RankSelect) {
+        let bits: Vec<bool> = (0..rank.len()).map(|i| rank.bits().get(i)).collect();
+        let ones: Vec<usize> = (0..bits.len()).filter(|&i| bits[i]).collect();
+        let zeros: Vec<usize> = (0..bits.len()).filter(|&i| !bits[i]).collect();
+
+        let mut count = 0;
+        for (i, &bit) in bits.iter().enumerate() {
+            assert_eq!(rank.rank1(i), count);
+            assert_eq!(rank.rank0(i), i - count);
+            count += bit as usize;
+        }
+        assert_eq!(rank.rank1(bits.len()), ones.len());
+        assert_eq!(rank.count_ones(), ones.len());
+        assert_eq!(rank.count_zeros(), zeros.len());
+
+        for (k, &pos) in ones.iter().enumerate() {
+            assert_eq!(rank.select1(k), Some(pos));
+        }
+        for (k, &pos) in zeros.iter().enumerate() {
+            assert_eq!(rank.select0(k), Some(pos));
+        }
+        assert_eq!(rank.select1(ones.len()), None);
+        assert_eq!(rank.select0(zeros.len()), None);
+    }
+
+    #[test]
+    fn test_random_patterns() {
+        let mut rng = Lcg(21);
+        for len in [0, 1, 63, 64, 65, 511, 512, 2047, 2048, 2049, 10_000] {
+            // Sparse, dense and balanced patterns.
+            for density in [1, 50, 99] {
+                let mut bits = BitArray::new(len);
+                for i in 0..len {
+                    if rng.next() % 100 < density {
+                        bits.set(i);
+                    }
+                }
+                check_against_naive(&RankSelect::new(bits));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ignores_tail_bits() {
+        let mut bits = BitArray::new(70);
+        bits.invert();
+        let rank = RankSelect::new(bits);
+        assert_eq!(rank.count_ones(), 70);
+        assert_eq!(rank.count_zeros(), 0);
+        assert_eq!(rank.select1(69), Some(69));
+        assert_eq!(rank.select1(70), None);
+        assert_eq!(rank.select0(0), None);
+        check_against_naive(&rank);
+    }
+
+    #[test]
+    fn test_directory_overhead() {
+        let len = 1 << 20;
+        let rank = RankSelect::new(BitArray::new(len));
+        let directory = rank.superblocks.len() * usize::BITS as usize + rank.blocks.len() * 16;
+        let overhead = directory as f64 / len as f64;
+        assert!((0.03..=0.25).contains(&overhead), "overhead {overhead}");
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_rank_out_of_bounds() {
+        RankSelect::new(BitArray::new(10)).rank1(11);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut bits = BitArray::new(3000);
+        for i in (0..3000).step_by(7) {
+            bits.set(i);
+        }
+        let rank = RankSelect::new(bits);
+        let json = serde_json::to_string(&rank).unwrap();
+
+        let back: RankSelect = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.count_ones(), rank.count_ones());
+        assert_eq!(back.select1(300), Some(2100));
+        assert_eq!(back.rank0(2500), rank.rank0(2500));
+    }
+}
@@ -1,6 +1,12 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cmp::Ordering, fmt, ops::RangeBounds};
 
-pub type Link<T> = Option<Rc<RefCell<AVLNode<T>>>>;
+use super::{
+    bst::{assert_sorted, count_in_range, SetOp},
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError, Violation},
+};
+
+type Link<T> = Option<Box<AVLNode<T>>>;
 
 #[derive(Debug)]
 pub(crate) struct AVLNode<T> {
@@ -8,6 +14,7 @@ pub(crate) struct AVLNode<T> {
     right: Link<T>,
     data: T,
     height: i32,
+    size: usize,
 }
 
 #[derive(Debug)]
@@ -16,30 +23,1001 @@ pub struct Avl<T> {
 }
 
 impl<T> AVLNode<T> {
-    fn new(data: T) -> Rc<RefCell<AVLNode<T>>> {
-        Rc::new(RefCell::new(AVLNode {
+    fn new(data: T) -> Box<AVLNode<T>> {
+        Box::new(AVLNode {
             left: None,
             right: None,
             data,
             height: 1,
-        }))
+            size: 1,
+        })
+    }
+
+    fn parts(&self) -> (&T, Option<&Self>, Option<&Self>) {
+        (&self.data, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+}
+
+impl<T: PartialOrd> Default for Avl<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl<T: PartialOrd + Clone> Avl<T> {
+impl<T: PartialOrd> Avl<T> {
     pub fn new() -> Self {
         Self { root: None }
     }
 
+    /// Checks ordering, the AVL balance condition and the cached heights and
+    /// subtree sizes, reporting the first node that breaks any of them.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            true,
+            AVLNode::parts,
+            |n, left, right| {
+                let (left_height, left_size): (usize, usize) = left.unwrap_or((0, 0));
+                let (right_height, right_size) = right.unwrap_or((0, 0));
+                let height = 1 + left_height.max(right_height);
+                let size = 1 + left_size + right_size;
+
+                if left_height.abs_diff(right_height) > 1 {
+                    return Err(Violation::Unbalanced {
+                        left: left_height,
+                        right: right_height,
+                    });
+                }
+                if n.height as usize != height {
+                    return Err(Violation::StaleHeight {
+                        cached: n.height as usize,
+                        actual: height,
+                    });
+                }
+                if n.size != size {
+                    return Err(Violation::StaleSize {
+                        cached: n.size,
+                        actual: size,
+                    });
+                }
+                Ok((height, size))
+            },
+        )
+        .map(|_| ())
+    }
+
+    /// Number of elements strictly less than `val`.
+    pub fn rank(&self, val: &T) -> usize {
+        self.count_less(val, false)
+    }
+
+    pub fn count_range<R: RangeBounds<T>>(&self, range: R) -> usize {
+        count_in_range(&range, self.len(), |val, inclusive| {
+            self.count_less(val, inclusive)
+        })
+    }
+
+    fn count_less(&self, val: &T, inclusive: bool) -> usize {
+        let mut link = &self.root;
+        let mut count = 0;
+        while let Some(node) = link {
+            if node.data < *val || (inclusive && node.data <= *val) {
+                count += Self::node_size(&node.left) + 1;
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        count
+    }
+
+    /// Builds a perfectly balanced tree from items in ascending order in
+    /// `O(n)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the items are not sorted.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        assert_sorted(&items);
+        let len = items.len();
+        Self {
+            root: Self::build(&mut items.into_iter(), len),
+        }
+    }
+
+    /// Moves every element of `other` into `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = Self::combine(self.root.take(), other.root.take(), SetOp::Append);
+    }
+
+    /// Splits the tree in two, returning every element greater than or equal
+    /// to `val` and keeping the smaller ones.
+    pub fn split_off(&mut self, val: &T) -> Self {
+        let (lower, upper) = Self::split(self.root.take(), &|data| data < val);
+        self.root = lower;
+        Self { root: upper }
+    }
+
+    /// Elements in either tree. Equal elements are matched up one to one, so
+    /// a value keeps the larger of its two counts.
+    pub fn union(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Union);
+        Self { root }
+    }
+
+    /// Elements in both trees, each with the smaller of its two counts.
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Intersection);
+        Self { root }
+    }
+
+    /// Elements of `self` that are not matched by an equal one in `other`.
+    pub fn difference(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Difference);
+        Self { root }
+    }
+
+    fn build(items: &mut impl Iterator<Item = T>, n: usize) -> Link<T> {
+        if n == 0 {
+            return None;
+        }
+
+        let left = Self::build(items, n / 2);
+        let mut node = AVLNode::new(items.next()?);
+        node.left = left;
+        node.right = Self::build(items, n - n / 2 - 1);
+        Self::update_node(&mut node);
+        Some(node)
+    }
+
+    /// Joins two trees around a detached `mid` node, where everything in
+    /// `left` sorts before `mid` and everything in `right` after it. The
+    /// shorter tree is hung off the spine of the taller one at the point
+    /// where the heights match, then rebalanced on the way back up.
+    fn join(left: Link<T>, mut mid: Box<AVLNode<T>>, right: Link<T>) -> Box<AVLNode<T>> {
+        let (left_height, right_height) = (Self::node_height(&left), Self::node_height(&right));
+        if left_height > right_height + 1 {
+            let mut left = left.unwrap();
+            let inner = left.right.take();
+            left.right = Some(Self::join(inner, mid, right));
+            Self::rebalance(left)
+        } else if right_height > left_height + 1 {
+            let mut right = right.unwrap();
+            let inner = right.left.take();
+            right.left = Some(Self::join(left, mid, inner));
+            Self::rebalance(right)
+        } else {
+            mid.left = left;
+            mid.right = right;
+            Self::update_node(&mut mid);
+            mid
+        }
+    }
+
+    /// Joins two trees where everything in `left` sorts before `right`.
+    fn join2(left: Link<T>, right: Link<T>) -> Link<T> {
+        match right {
+            Some(right) => {
+                let (rest, min) = Self::remove_min(right);
+                Some(Self::join(left, min, rest))
+            }
+            None => left,
+        }
+    }
+
+    /// Splits `node` into the elements satisfying `goes_left` and the rest.
+    /// `goes_left` must hold for a prefix of the elements in order.
+    fn split(node: Link<T>, goes_left: &impl Fn(&T) -> bool) -> (Link<T>, Link<T>) {
+        let Some(mut n) = node else {
+            return (None, None);
+        };
+
+        let left = n.left.take();
+        let right = n.right.take();
+        if goes_left(&n.data) {
+            let (lower, upper) = Self::split(right, goes_left);
+            (Some(Self::join(left, n, lower)), upper)
+        } else {
+            let (lower, upper) = Self::split(left, goes_left);
+            (lower, Some(Self::join(upper, n, right)))
+        }
+    }
+
+    // Join-based set operation: split `b` around the root of `a`, combine
+    // the halves recursively and join the results back together. Elements
+    // equal to the root are gathered from both trees and resolved by `op`.
+    fn combine(a: Link<T>, b: Link<T>, op: SetOp) -> Link<T> {
+        let (mut root, b) = match (a, b) {
+            (None, b) if matches!(op, SetOp::Union | SetOp::Append) => return b,
+            (None, _) => return None,
+            (_, None) if op == SetOp::Intersection => return None,
+            (a, None) => return a,
+            (Some(root), b) => (root, b),
+        };
+
+        let left = root.left.take();
+        let right = root.right.take();
+        let (a_lt, a_eq_left, a_eq_right, a_gt, b_lt, b_eq, b_gt) = {
+            let pivot = &root.data;
+            let (a_lt, a_eq_left) = Self::split(left, &|x| x < pivot);
+            let (a_eq_right, a_gt) = Self::split(right, &|x| x <= pivot);
+            let (b_lt, rest) = Self::split(b, &|x| x < pivot);
+            let (b_eq, b_gt) = Self::split(rest, &|x| x <= pivot);
+            (a_lt, a_eq_left, a_eq_right, a_gt, b_lt, b_eq, b_gt)
+        };
+
+        let a_eq = Some(Self::join(a_eq_left, root, a_eq_right));
+        let (a_count, b_count) = (Self::node_size(&a_eq), Self::node_size(&b_eq));
+        let mid = match op {
+            SetOp::Union if a_count >= b_count => a_eq,
+            SetOp::Union => b_eq,
+            SetOp::Intersection if a_count <= b_count => a_eq,
+            SetOp::Intersection => b_eq,
+            SetOp::Difference => {
+                let mut rest = a_eq;
+                for _ in 0..b_count {
+                    rest = match rest {
+                        Some(n) => Self::remove_min(n).0,
+                        None => break,
+                    };
+                }
+                rest
+            }
+            SetOp::Append => Self::join2(a_eq, b_eq),
+        };
+
+        let lower = Self::combine(a_lt, b_lt, op);
+        let upper = Self::combine(a_gt, b_gt, op);
+        Self::join2(Self::join2(lower, mid), upper)
+    }
+
     pub fn insert(&mut self, val: T) {
         let node = AVLNode::new(val);
-        match self.root {
-            Some(ref root) => Self::insert_node(root, node),
-            None => self.root = Some(node),
+        self.root = Some(Self::insert_node(self.root.take(), node));
+    }
+
+    fn insert_node(curr: Link<T>, node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        let Some(mut curr) = curr else {
+            return node;
+        };
+
+        if node.data < curr.data {
+            curr.left = Some(Self::insert_node(curr.left.take(), node));
+        } else {
+            curr.right = Some(Self::insert_node(curr.right.take(), node));
         }
+
+        Self::rebalance(curr)
+    }
+
+    pub fn search(&self, val: T) -> bool {
+        Self::search_node(&self.root, val)
     }
 
-    fn insert_node(curr: &Rc<RefCell<AVLNode<T>>>, node: Rc<RefCell<AVLNode<T>>>) {
-        
+    fn search_node(curr: &Link<T>, val: T) -> bool {
+        match curr {
+            Some(node) => {
+                if val < node.data {
+                    Self::search_node(&node.left, val)
+                } else if val > node.data {
+                    Self::search_node(&node.right, val)
+                } else {
+                    true
+                }
+            }
+            None => false,
+        }
+    }
+
+    pub fn delete(&mut self, val: T) {
+        self.root = Self::delete_node(self.root.take(), &val);
+    }
+
+    fn delete_node(node: Link<T>, val: &T) -> Link<T> {
+        let mut n = node?;
+        if *val < n.data {
+            n.left = Self::delete_node(n.left.take(), val);
+        } else if *val > n.data {
+            n.right = Self::delete_node(n.right.take(), val);
+        } else {
+            let (left, right) = match (n.left.take(), n.right.take()) {
+                (None, right) => return right,
+                (left, None) => return left,
+                (Some(left), Some(right)) => (left, right),
+            };
+
+            // Splice the in-order successor into the removed node's place
+            // instead of copying its data, so `T` does not need `Clone`.
+            let (new_right, mut successor) = Self::remove_min(right);
+            successor.left = Some(left);
+            successor.right = new_right;
+            return Some(Self::rebalance(successor));
+        }
+
+        Some(Self::rebalance(n))
+    }
+
+    fn remove_min(mut node: Box<AVLNode<T>>) -> (Link<T>, Box<AVLNode<T>>) {
+        match node.left.take() {
+            Some(left) => {
+                let (new_left, min) = Self::remove_min(left);
+                node.left = new_left;
+                (Some(Self::rebalance(node)), min)
+            }
+            None => (node.right.take(), node),
+        }
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        Self::inorder(&self.root, visit_fn);
+    }
+
+    fn inorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
+        if let Some(n) = node {
+            Self::inorder(&n.left, visit_fn);
+            visit_fn(&n.data);
+            Self::inorder(&n.right, visit_fn);
+        }
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        Self::preorder(&self.root, visit_fn);
+    }
+
+    fn preorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
+        if let Some(n) = node {
+            visit_fn(&n.data);
+            Self::preorder(&n.left, visit_fn);
+            Self::preorder(&n.right, visit_fn);
+        }
+    }
+
+    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        Self::postorder(&self.root, visit_fn);
+    }
+
+    fn postorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
+        if let Some(n) = node {
+            Self::postorder(&n.left, visit_fn);
+            Self::postorder(&n.right, visit_fn);
+            visit_fn(&n.data);
+        }
+    }
+
+    fn update_node(n: &mut AVLNode<T>) {
+        n.height = 1 + Self::node_height(&n.left).max(Self::node_height(&n.right));
+        n.size = 1 + Self::node_size(&n.left) + Self::node_size(&n.right);
+    }
+
+    fn balance_factor(n: &AVLNode<T>) -> i32 {
+        Self::node_height(&n.left) - Self::node_height(&n.right)
+    }
+
+    fn rebalance(mut node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        Self::update_node(&mut node);
+        let balance = Self::balance_factor(&node);
+
+        if balance > 1 {
+            if Self::balance_factor(node.left.as_ref().unwrap()) < 0 {
+                Self::rotate_left_right(node)
+            } else {
+                Self::rotate_right(node)
+            }
+        } else if balance < -1 {
+            if Self::balance_factor(node.right.as_ref().unwrap()) > 0 {
+                Self::rotate_right_left(node)
+            } else {
+                Self::rotate_left(node)
+            }
+        } else {
+            node
+        }
+    }
+
+    fn rotate_right(mut node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        let mut pivot = node.left.take().unwrap();
+        node.left = pivot.right.take();
+        Self::update_node(&mut node);
+        pivot.right = Some(node);
+        Self::update_node(&mut pivot);
+        pivot
+    }
+
+    fn rotate_left(mut node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        let mut pivot = node.right.take().unwrap();
+        node.right = pivot.left.take();
+        Self::update_node(&mut node);
+        pivot.left = Some(node);
+        Self::update_node(&mut pivot);
+        pivot
+    }
+
+    fn rotate_left_right(mut node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        let left = node.left.take().unwrap();
+        node.left = Some(Self::rotate_left(left));
+        Self::rotate_right(node)
+    }
+
+    fn rotate_right_left(mut node: Box<AVLNode<T>>) -> Box<AVLNode<T>> {
+        let right = node.right.take().unwrap();
+        node.right = Some(Self::rotate_right(right));
+        Self::rotate_left(node)
+    }
+}
+
+impl<T> Avl<T> {
+    pub fn len(&self) -> usize {
+        Self::node_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, so an empty tree has height zero.
+    pub fn height(&self) -> usize {
+        Self::node_height(&self.root) as usize
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), AVLNode::children)
+    }
+
+    /// Mean number of nodes visited by a successful search.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    /// Returns the `k`-th smallest element, counting from zero.
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let left_size = Self::node_size(&node.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => link = &node.left,
+                Ordering::Equal => return Some(&node.data),
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    link = &node.right;
+                }
+            }
+        }
+        None
+    }
+
+    fn node_height(node: &Link<T>) -> i32 {
+        node.as_ref().map_or(0, |n| n.height)
+    }
+
+    fn node_size(node: &Link<T>) -> usize {
+        node.as_ref().map_or(0, |n| n.size)
+    }
+}
+
+impl<T: fmt::Display> Avl<T> {
+    /// Renders the tree as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &AVLNode<T>| n.data.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            AVLNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Avl<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &AVLNode<T>| n.data.to_string();
+        render::write_ascii(f, self.root.as_deref(), AVLNode::children, label)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Avl;
+
+    // Serialized as a sequence in ascending order.
+    impl<T: Serialize + PartialOrd> Serialize for Avl<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.len()))?;
+            let mut result = Ok(());
+            self.inorder_traversal(&mut |val| {
+                if result.is_ok() {
+                    result = seq.serialize_element(val);
+                }
+            });
+            result?;
+            seq.end()
+        }
+    }
+
+    impl<'de, T: Deserialize<'de> + PartialOrd> Deserialize<'de> for Avl<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let items = Vec::<T>::deserialize(deserializer)?;
+            if !items.windows(2).all(|w| w[0] <= w[1]) {
+                return Err(de::Error::custom("elements are not in ascending order"));
+            }
+            Ok(Self::from_sorted_iter(items))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_trees::Violation;
+    use crate::test_util::Lcg;
+
+    // Returns the (height, size) of the subtree after checking its ordering,
+    // balance and cached metadata.
+    fn check_node(node: &Link<i32>, lo: Option<i32>, hi: Option<i32>) -> (i32, usize) {
+        match node {
+            Some(n) => {
+                if let Some(lo) = lo {
+                    assert!(n.data >= lo);
+                }
+                if let Some(hi) = hi {
+                    assert!(n.data <= hi);
+                }
+
+                let (lh, ls) = check_node(&n.left, lo, Some(n.data));
+                let (rh, rs) = check_node(&n.right, Some(n.data), hi);
+                assert!((lh - rh).abs() <= 1, "unbalanced at {}", n.data);
+                assert_eq!(n.height, 1 + lh.max(rh), "stale height at {}", n.data);
+                assert_eq!(n.size, 1 + ls + rs, "stale size at {}", n.data);
+                (n.height, n.size)
+            }
+            None => (0, 0),
+        }
+    }
+
+    fn assert_avl(tree: &Avl<i32>) {
+        check_node(&tree.root, None, None);
+    }
+
+    fn inorder(tree: &Avl<i32>) -> Vec<i32> {
+        let mut elements = vec![];
+        tree.inorder_traversal(&mut |x| elements.push(*x));
+        elements
+    }
+
+    #[test]
+    fn test_insert_and_search() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7, 2, 4, 6, 8] {
+            tree.insert(val);
+        }
+
+        for val in [5, 3, 7, 2, 4, 6, 8] {
+            assert!(tree.search(val));
+        }
+        assert!(!tree.search(10));
+        assert!(!tree.search(0));
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_rotate_right() {
+        let mut tree = Avl::new();
+        tree.insert(3);
+        tree.insert(2);
+        tree.insert(1);
+
+        assert_eq!(tree.root.as_ref().unwrap().data, 2);
+        assert_eq!(tree.height(), 2);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_rotate_left() {
+        let mut tree = Avl::new();
+        tree.insert(1);
+        tree.insert(2);
+        tree.insert(3);
+
+        assert_eq!(tree.root.as_ref().unwrap().data, 2);
+        assert_eq!(tree.height(), 2);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_rotate_left_right() {
+        let mut tree = Avl::new();
+        tree.insert(3);
+        tree.insert(1);
+        tree.insert(2);
+
+        assert_eq!(tree.root.as_ref().unwrap().data, 2);
+        assert_eq!(tree.height(), 2);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_rotate_right_left() {
+        let mut tree = Avl::new();
+        tree.insert(1);
+        tree.insert(3);
+        tree.insert(2);
+
+        assert_eq!(tree.root.as_ref().unwrap().data, 2);
+        assert_eq!(tree.height(), 2);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_sorted_insert_stays_balanced() {
+        let mut tree = Avl::new();
+        for val in 0..1023 {
+            tree.insert(val);
+        }
+
+        assert_eq!(tree.height(), 10);
+        assert_eq!(inorder(&tree), (0..1023).collect::<Vec<_>>());
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_traversals() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7, 2, 4, 6, 8] {
+            tree.insert(val);
+        }
+
+        let mut pre = vec![];
+        tree.preorder_traversal(&mut |x| pre.push(*x));
+        let mut post = vec![];
+        tree.postorder_traversal(&mut |x| post.push(*x));
+
+        assert_eq!(inorder(&tree), vec![2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(pre, vec![5, 3, 2, 4, 7, 6, 8]);
+        assert_eq!(post, vec![2, 4, 3, 6, 8, 7, 5]);
+    }
+
+    #[test]
+    fn test_delete_leaf_node() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7, 2] {
+            tree.insert(val);
+        }
+
+        tree.delete(2);
+
+        assert!(!tree.search(2));
+        assert_eq!(inorder(&tree), vec![3, 5, 7]);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_delete_node_with_two_children() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7, 6, 8] {
+            tree.insert(val);
+        }
+
+        tree.delete(7);
+
+        assert!(!tree.search(7));
+        assert_eq!(inorder(&tree), vec![3, 5, 6, 8]);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_delete_root_node() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7] {
+            tree.insert(val);
+        }
+
+        tree.delete(5);
+
+        assert!(!tree.search(5));
+        assert_eq!(inorder(&tree), vec![3, 7]);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_delete_missing_value() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 7] {
+            tree.insert(val);
+        }
+
+        tree.delete(42);
+
+        assert_eq!(inorder(&tree), vec![3, 5, 7]);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_delete_triggers_rebalance() {
+        let mut tree = Avl::new();
+        for val in [5, 3, 8, 7, 9, 10] {
+            tree.insert(val);
+        }
+
+        tree.delete(3);
+
+        assert_eq!(inorder(&tree), vec![5, 7, 8, 9, 10]);
+        assert_avl(&tree);
+    }
+
+    #[test]
+    fn test_randomized_insert_delete_balance() {
+        let mut rng = Lcg(0x5eed);
+        let mut tree = Avl::new();
+        let mut reference: Vec<i32> = Vec::new();
+
+        for _ in 0..5000 {
+            let val = (rng.next() % 500) as i32;
+            if rng.next().is_multiple_of(3) {
+                tree.delete(val);
+                if let Some(pos) = reference.iter().position(|&x| x == val) {
+                    reference.remove(pos);
+                }
+            } else {
+                tree.insert(val);
+                reference.push(val);
+            }
+            assert_avl(&tree);
+        }
+
+        reference.sort();
+        assert_eq!(inorder(&tree), reference);
+        assert_eq!(tree.len(), reference.len());
+        for (k, val) in reference.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(val));
+        }
+    }
+
+    #[test]
+    fn test_select_and_rank() {
+        let mut tree = Avl::new();
+        for val in [50, 20, 80, 10, 30, 70, 90] {
+            tree.insert(val);
+        }
+
+        let sorted = [10, 20, 30, 50, 70, 80, 90];
+        for (k, val) in sorted.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(val));
+            assert_eq!(tree.rank(val), k);
+        }
+        assert_eq!(tree.select(7), None);
+        assert_eq!(tree.rank(&55), 4);
+        assert_eq!(tree.len(), 7);
+    }
+
+    #[test]
+    fn test_count_range() {
+        let mut tree = Avl::new();
+        for val in 0..100 {
+            tree.insert(val);
+        }
+
+        assert_eq!(tree.count_range(10..20), 10);
+        assert_eq!(tree.count_range(10..=20), 11);
+        assert_eq!(tree.count_range(..50), 50);
+        assert_eq!(tree.count_range(95..), 5);
+        assert_eq!(tree.count_range(..), 100);
+        assert_eq!(tree.count_range(200..300), 0);
+    }
+
+    #[test]
+    fn test_sizes_across_rotations() {
+        let mut tree = Avl::new();
+        for val in (0..200).rev() {
+            tree.insert(val);
+            assert_avl(&tree);
+        }
+        for val in (0..200).step_by(2) {
+            tree.delete(val);
+            assert_avl(&tree);
+        }
+
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.select(0), Some(&1));
+        assert_eq!(tree.rank(&101), 50);
+    }
+
+    fn random_tree(rng: &mut Lcg, n: usize, modulus: u64) -> (Avl<i32>, Vec<i32>) {
+        let mut tree = Avl::new();
+        let mut values = vec![];
+        for _ in 0..n {
+            let val = (rng.next() % modulus) as i32;
+            tree.insert(val);
+            values.push(val);
+        }
+        values.sort();
+        (tree, values)
+    }
+
+    #[test]
+    fn test_from_sorted_iter() {
+        let tree = Avl::from_sorted_iter(0..1000);
+        assert_avl(&tree);
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.height(), 10);
+        assert_eq!(inorder(&tree), (0..1000).collect::<Vec<_>>());
+
+        let dups = Avl::from_sorted_iter([1, 1, 2, 2, 2, 3]);
+        assert_avl(&dups);
+        assert_eq!(inorder(&dups), vec![1, 1, 2, 2, 2, 3]);
+        assert!(Avl::<i32>::from_sorted_iter([]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ascending order")]
+    fn test_from_sorted_iter_rejects_unsorted() {
+        Avl::from_sorted_iter([1, 3, 2]);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut rng = Lcg(11);
+        for pivot in [-1, 0, 17, 50, 99, 100] {
+            let (mut tree, values) = random_tree(&mut rng, 300, 100);
+            let upper = tree.split_off(&pivot);
+            assert_avl(&tree);
+            assert_avl(&upper);
+
+            let (below, above): (Vec<i32>, Vec<i32>) = values.iter().partition(|&&x| x < pivot);
+            assert_eq!(inorder(&tree), below);
+            assert_eq!(inorder(&upper), above);
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let mut tree = Avl::from_sorted_iter(0..50);
+        let mut other = Avl::from_sorted_iter(50..60);
+        tree.append(&mut other);
+        assert!(other.is_empty());
+        assert_avl(&tree);
+        assert_eq!(inorder(&tree), (0..60).collect::<Vec<_>>());
+
+        let mut overlapping = Avl::from_sorted_iter((0..30).map(|x| x * 2));
+        tree.append(&mut overlapping);
+        assert_avl(&tree);
+        assert_eq!(tree.len(), 90);
+        assert_eq!(tree.count_range(10..=10), 2);
+    }
+
+    #[test]
+    fn test_set_operations_against_counts() {
+        use std::collections::BTreeMap;
+
+        let mut rng = Lcg(5);
+        for _ in 0..20 {
+            let na = (rng.next() % 150) as usize;
+            let nb = (rng.next() % 150) as usize;
+            let (a, a_vals) = random_tree(&mut rng, na, 60);
+            let (b, b_vals) = random_tree(&mut rng, nb, 60);
+
+            let mut counts: BTreeMap<i32, (usize, usize)> = BTreeMap::new();
+            for x in &a_vals {
+                counts.entry(*x).or_default().0 += 1;
+            }
+            for x in &b_vals {
+                counts.entry(*x).or_default().1 += 1;
+            }
+            let expect = |f: fn(usize, usize) -> usize| -> Vec<i32> {
+                counts
+                    .iter()
+                    .flat_map(|(x, &(ca, cb))| std::iter::repeat_n(*x, f(ca, cb)))
+                    .collect()
+            };
+
+            let union =
+                Avl::from_sorted_iter(a_vals.clone()).union(Avl::from_sorted_iter(b_vals.clone()));
+            assert_avl(&union);
+            assert_eq!(inorder(&union), expect(|ca, cb| ca.max(cb)));
+
+            let both = Avl::from_sorted_iter(a_vals.clone())
+                .intersection(Avl::from_sorted_iter(b_vals.clone()));
+            assert_avl(&both);
+            assert_eq!(inorder(&both), expect(|ca, cb| ca.min(cb)));
+
+            let diff = a.difference(b);
+            assert_avl(&diff);
+            assert_eq!(inorder(&diff), expect(|ca, cb| ca.saturating_sub(cb)));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let (tree, _) = random_tree(&mut Lcg(21), 500, 200);
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.average_search_depth() <= tree.height() as f64);
+        assert_eq!(tree.depth_histogram().len(), tree.height());
+        assert_eq!(tree.depth_histogram().iter().sum::<usize>(), tree.len());
+
+        let mut tree = Avl::from_sorted_iter(1..=7);
+        assert_eq!(tree.depth_histogram(), vec![1, 2, 4]);
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().height = 3;
+        let err = tree.validate().unwrap_err();
+        assert_eq!(err.path, "L");
+        assert_eq!(
+            err.violation,
+            Violation::StaleHeight {
+                cached: 3,
+                actual: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_detects_imbalance() {
+        // Hang a new leaf under 1 and detach 3, leaving cached metadata that
+        // is accurate but describes a lopsided tree.
+        let mut tree = Avl::from_sorted_iter([1, 2, 3]);
+        let root = tree.root.as_mut().unwrap();
+        root.right = None;
+        root.height = 3;
+        let left = root.left.as_mut().unwrap();
+        left.left = Some(AVLNode::new(0));
+        left.height = 2;
+        left.size = 2;
+        assert_eq!(
+            tree.validate().unwrap_err().violation,
+            Violation::Unbalanced { left: 2, right: 0 }
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut tree = Avl::new();
+        for k in 0..100 {
+            tree.insert(k);
+        }
+        let dot = tree.to_dot_with(DotOptions {
+            balance: true,
+            ..DotOptions::default()
+        });
+        // Every balance factor of an AVL tree is -1, 0 or 1.
+        let factors: Vec<_> = dot
+            .match_indices("b=")
+            .map(|(i, _)| &dot[i + 2..i + 4])
+            .collect();
+        assert_eq!(factors.len(), 100);
+        assert!(factors.iter().all(|b| ["-1", "0\"", "1\""].contains(b)));
+        assert_eq!(
+            tree.to_string()
+                .lines()
+                .filter(|l| !l.ends_with("nil"))
+                .count(),
+            100
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut tree = Avl::new();
+        for i in 0..50 {
+            tree.insert((i * 13) % 50);
+        }
+        let json = serde_json::to_string(&tree).unwrap();
+        let expected: Vec<i32> = (0..50).collect();
+        assert_eq!(json, serde_json::to_string(&expected).unwrap());
+
+        let back: Avl<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 50);
+        assert!((0..50).all(|i| back.select(i as usize) == Some(&i)));
+        assert_eq!(back.validate(), Ok(()));
+        assert!(serde_json::from_str::<Avl<i32>>("[2,1]").is_err());
     }
 }
@@ -1,153 +1,1082 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    fmt,
+    iter::FusedIterator,
+    mem,
+    ops::{Bound, RangeBounds},
+    ptr,
+};
 
-pub type Link<T> = Option<Rc<RefCell<BSTNode<T>>>>;
+use super::{
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError},
+};
+
+pub(crate) type Link<T> = Option<Box<BSTNode<T>>>;
 
-#[derive(Debug)]
 pub(crate) struct BSTNode<T> {
     left: Link<T>,
     right: Link<T>,
-    data: T,
+    pub(crate) data: T,
+    size: usize,
 }
 
-#[derive(Debug)]
 pub struct Bst<T> {
     root: Link<T>,
 }
 
 impl<T> BSTNode<T> {
-    fn new(data: T) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(BSTNode {
+    pub(crate) fn new(data: T) -> Box<Self> {
+        Box::new(BSTNode {
             left: None,
             right: None,
             data,
+            size: 1,
+        })
+    }
+
+    pub(crate) fn size(link: &Link<T>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    /// Descends from `curr` using `cmp`, which orders the probe against the
+    /// data stored in a node, and returns the node it compares equal to.
+    pub(crate) fn find<'a>(
+        mut curr: &'a Link<T>,
+        cmp: &mut impl FnMut(&T) -> Ordering,
+    ) -> Option<&'a Self> {
+        while let Some(node) = curr {
+            curr = match cmp(&node.data) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    pub(crate) fn find_mut<'a>(
+        mut curr: &'a mut Link<T>,
+        cmp: &mut impl FnMut(&T) -> Ordering,
+    ) -> Option<&'a mut Self> {
+        while let Some(node) = curr {
+            curr = match cmp(&node.data) {
+                Ordering::Less => &mut node.left,
+                Ordering::Greater => &mut node.right,
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    /// Links `data` in as a new leaf below `root` and returns a reference to
+    /// it. Equal elements go to the right, so insertion order is kept among
+    /// duplicates.
+    pub(crate) fn insert<'a>(
+        mut root: &'a mut Link<T>,
+        data: T,
+        cmp: &mut impl FnMut(&T, &T) -> Ordering,
+    ) -> &'a mut T {
+        while let Some(node) = root {
+            node.size += 1;
+            root = if cmp(&data, &node.data) == Ordering::Less {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+        &mut root.insert(Self::new(data)).data
+    }
+
+    /// Unlinks the node `cmp` compares equal to below `root` and returns its
+    /// data. A node with two children is replaced by its in-order successor
+    /// node, so no data is copied.
+    pub(crate) fn remove(root: &mut Link<T>, cmp: &mut impl FnMut(&T) -> Ordering) -> Option<T> {
+        // Sizes are decremented on the way down, so make sure there is
+        // something to remove first.
+        Self::find(root, cmp)?;
+
+        let mut link = root;
+        loop {
+            let ord = cmp(&link.as_ref()?.data);
+            if ord == Ordering::Equal {
+                break;
+            }
+            let node = link.as_mut()?;
+            node.size -= 1;
+            link = if ord == Ordering::Less {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+
+        let mut node = link.take()?;
+        *link = match (node.left.take(), node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(left), right) => {
+                let mut right = right;
+                let mut successor = Self::take_min(&mut right)?;
+                successor.left = Some(left);
+                successor.right = right;
+                successor.update_size();
+                Some(successor)
+            }
+        };
+        Some(node.data)
+    }
+
+    pub(crate) fn inorder(mut link: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
+        let mut stack = Vec::new();
+        loop {
+            while let Some(node) = link {
+                stack.push(node);
+                link = &node.left;
+            }
+            match stack.pop() {
+                Some(node) => {
+                    visit_fn(&node.data);
+                    link = &node.right;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Unlinks the smallest node below `link`.
+    fn take_min(mut link: &mut Link<T>) -> Option<Box<Self>> {
+        while link.as_ref()?.left.is_some() {
+            let node = link.as_mut()?;
+            node.size -= 1;
+            link = &mut node.left;
+        }
+        let mut min = link.take()?;
+        *link = min.right.take();
+        Some(min)
+    }
+
+    /// Unlinks the largest node below `link`.
+    fn take_max(mut link: &mut Link<T>) -> Option<Box<Self>> {
+        while link.as_ref()?.right.is_some() {
+            let node = link.as_mut()?;
+            node.size -= 1;
+            link = &mut node.right;
+        }
+        let mut max = link.take()?;
+        *link = max.left.take();
+        Some(max)
+    }
+
+    /// Checks that keys are ordered against their ancestors and that the
+    /// cached subtree sizes are correct.
+    pub(crate) fn validate<'a, K: PartialOrd + 'a>(
+        root: &'a Link<T>,
+        allow_equal: bool,
+        key: impl Fn(&'a T) -> &'a K,
+    ) -> Result<(), InvariantError> {
+        let parts = |n: &'a Self| (key(&n.data), n.left.as_deref(), n.right.as_deref());
+        walk(root.as_deref(), allow_equal, parts, |n, left, right| {
+            validate::check_size(n.size, left, right)
+        })
+        .map(|_| ())
+    }
+
+    pub(crate) fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    pub(crate) fn depth_histogram(root: &Link<T>) -> Vec<usize> {
+        validate::depth_histogram(root.as_deref(), Self::children)
+    }
+
+    /// Builds a perfectly balanced tree from the next `n` items, which must
+    /// already be in order. Recursion depth is logarithmic in `n`.
+    pub(crate) fn build(items: &mut impl Iterator<Item = T>, n: usize) -> Link<T> {
+        if n == 0 {
+            return None;
+        }
+
+        let left = Self::build(items, n / 2);
+        let data = items.next()?;
+        let right = Self::build(items, n - n / 2 - 1);
+        Some(Box::new(BSTNode {
+            left,
+            right,
+            data,
+            size: n,
         }))
     }
+
+    /// Splits `link` into the nodes satisfying `goes_left` and the rest,
+    /// walking a single path. `goes_left` must hold for a prefix of the
+    /// elements.
+    fn split(mut link: Link<T>, goes_left: impl Fn(&T) -> bool) -> (Link<T>, Link<T>) {
+        let mut path = Vec::new();
+        while let Some(mut node) = link {
+            let left = goes_left(&node.data);
+            link = if left {
+                node.right.take()
+            } else {
+                node.left.take()
+            };
+            path.push((node, left));
+        }
+
+        // Reassemble bottom-up: each node keeps its untouched side and takes
+        // the matching half of the subtree that was split below it.
+        let (mut lower, mut upper) = (None, None);
+        while let Some((mut node, goes_left)) = path.pop() {
+            if goes_left {
+                node.right = lower;
+                node.update_size();
+                lower = Some(node);
+            } else {
+                node.left = upper;
+                node.update_size();
+                upper = Some(node);
+            }
+        }
+        (lower, upper)
+    }
+
+    /// Hangs `right` below the maximum of `left`. Nothing in `left` may be
+    /// greater than anything in `right`.
+    fn join(mut left: Link<T>, right: Link<T>) -> Link<T> {
+        let extra = Self::size(&right);
+        let mut link = &mut left;
+        while let Some(node) = link {
+            node.size += extra;
+            link = &mut node.right;
+        }
+        *link = right;
+        left
+    }
+
+    /// Frees a subtree without recursing: left children are rotated up until
+    /// the current node has none, then it is dropped and its right child is
+    /// processed the same way.
+    pub(crate) fn drop_all(mut link: Link<T>) {
+        while let Some(mut node) = link {
+            link = match node.left.take() {
+                Some(mut left) => {
+                    node.left = left.right.take();
+                    left.right = Some(node);
+                    Some(left)
+                }
+                None => node.right.take(),
+            };
+        }
+    }
+}
+
+pub(crate) fn partial_order<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    if a < b {
+        Ordering::Less
+    } else if a > b {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// How two ordered collections are combined. Equal elements are matched up
+/// one to one, so with duplicates these are multiset operations: a union
+/// keeps the larger count of each value, an intersection the smaller, a
+/// difference subtracts and an append keeps both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+    Append,
+}
+
+/// Merges two sorted sequences according to `op`, keeping the result
+/// sorted.
+pub(crate) fn merge_sorted<T: PartialOrd>(
+    a: impl IntoIterator<Item = T>,
+    b: impl IntoIterator<Item = T>,
+    op: SetOp,
+) -> Vec<T> {
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    let keep_a = op != SetOp::Intersection;
+    let keep_b = matches!(op, SetOp::Union | SetOp::Append);
+    let mut merged = Vec::new();
+
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        match partial_order(x, y) {
+            Ordering::Less => {
+                let x = a.next().unwrap();
+                if keep_a {
+                    merged.push(x);
+                }
+            }
+            Ordering::Greater => {
+                let y = b.next().unwrap();
+                if keep_b {
+                    merged.push(y);
+                }
+            }
+            Ordering::Equal => {
+                let (x, y) = (a.next().unwrap(), b.next().unwrap());
+                match op {
+                    SetOp::Union | SetOp::Intersection => merged.push(x),
+                    SetOp::Difference => {}
+                    SetOp::Append => merged.extend([x, y]),
+                }
+            }
+        }
+    }
+
+    if keep_a {
+        merged.extend(a);
+    }
+    if keep_b {
+        merged.extend(b);
+    }
+    merged
+}
+
+pub(crate) fn assert_sorted<T: PartialOrd>(items: &[T]) {
+    assert!(
+        items.windows(2).all(|w| w[0] <= w[1]),
+        "from_sorted_iter requires the items to be in ascending order"
+    );
+}
+
+impl<T: PartialOrd> Default for Bst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<T: PartialOrd + Clone> Bst<T> {
+impl<T: PartialOrd> Bst<T> {
     pub fn new() -> Self {
         Self { root: None }
     }
 
     pub fn insert(&mut self, val: T) {
-        let node = BSTNode::new(val);
-        match self.root {
-            Some(ref root) => Self::insert_node(root, node),
-            None => self.root = Some(node),
+        BSTNode::insert(&mut self.root, val, &mut partial_order);
+    }
+
+    pub fn search(&self, val: T) -> bool {
+        BSTNode::find(&self.root, &mut |data| partial_order(&val, data)).is_some()
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        BSTNode::inorder(&self.root, visit_fn);
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        self.preorder_iter().for_each(visit_fn);
+    }
+
+    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        self.postorder_iter().for_each(visit_fn);
+    }
+
+    pub fn delete(&mut self, val: T) {
+        BSTNode::remove(&mut self.root, &mut |data| partial_order(&val, data));
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref left) = node.left {
+            node = left;
+        }
+        Some(&node.data)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref right) = node.right {
+            node = right;
+        }
+        Some(&node.data)
+    }
+
+    /// Largest element less than or equal to `val`.
+    pub fn floor(&self, val: &T) -> Option<&T> {
+        self.closest(|data| data <= val, true)
+    }
+
+    /// Smallest element greater than or equal to `val`.
+    pub fn ceiling(&self, val: &T) -> Option<&T> {
+        self.closest(|data| data >= val, false)
+    }
+
+    /// Largest element strictly less than `val`.
+    pub fn predecessor(&self, val: &T) -> Option<&T> {
+        self.closest(|data| data < val, true)
+    }
+
+    /// Smallest element strictly greater than `val`.
+    pub fn successor(&self, val: &T) -> Option<&T> {
+        self.closest(|data| data > val, false)
+    }
+
+    // Walks a single root-to-leaf path remembering the last node that matched
+    // `accept`. Matching nodes send the search towards the boundary (right
+    // for `from_below`, left otherwise), so the last match is the closest one.
+    fn closest(&self, accept: impl Fn(&T) -> bool, from_below: bool) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            let accepted = accept(&node.data);
+            if accepted {
+                best = Some(&node.data);
+            }
+            link = if accepted == from_below {
+                &node.right
+            } else {
+                &node.left
+            };
+        }
+        best
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        BSTNode::take_min(&mut self.root).map(|min| min.data)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        BSTNode::take_max(&mut self.root).map(|max| max.data)
+    }
+
+    /// Number of elements strictly less than `val`.
+    pub fn rank(&self, val: &T) -> usize {
+        self.count_less(val, false)
+    }
+
+    pub fn count_range<R: RangeBounds<T>>(&self, range: R) -> usize {
+        count_in_range(&range, self.len(), |val, inclusive| {
+            self.count_less(val, inclusive)
+        })
+    }
+
+    fn count_less(&self, val: &T, inclusive: bool) -> usize {
+        let mut link = &self.root;
+        let mut count = 0;
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            if node.data < *val || (inclusive && node.data <= *val) {
+                count += BSTNode::size(&node.left) + 1;
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
         }
+        count
+    }
+
+    /// Checks that every element is ordered against its ancestors and that
+    /// the cached subtree sizes are correct, reporting the first node that
+    /// breaks either.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        BSTNode::validate(&self.root, true, |data| data)
     }
 
-    fn insert_node(curr: &Rc<RefCell<BSTNode<T>>>, node: Rc<RefCell<BSTNode<T>>>) {
-        let mut borrowed_val = curr.borrow_mut();
-        if node.borrow().data < borrowed_val.data {
-            match borrowed_val.left {
-                Some(ref left) => Self::insert_node(left, node),
-                None => borrowed_val.left = Some(node),
+    /// Builds a perfectly balanced tree from items in ascending order in
+    /// `O(n)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the items are not sorted.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        assert_sorted(&items);
+        let len = items.len();
+        Self {
+            root: BSTNode::build(&mut items.into_iter(), len),
+        }
+    }
+
+    /// Moves every element of `other` into `self`, leaving `other` empty.
+    /// When all of `other` sorts after `self` its root is linked in below the
+    /// maximum; otherwise both are merged into a new balanced tree.
+    pub fn append(&mut self, other: &mut Self) {
+        let (Some(max), Some(other_min)) = (self.max(), other.min()) else {
+            if self.is_empty() {
+                mem::swap(self, other);
             }
+            return;
+        };
+
+        if max <= other_min {
+            self.root = BSTNode::join(self.root.take(), other.root.take());
+        } else {
+            let merged = merge_sorted(mem::take(self), mem::take(other), SetOp::Append);
+            *self = Self::from_sorted_iter(merged);
+        }
+    }
+
+    /// Splits the tree in two, returning every element greater than or equal
+    /// to `val` and keeping the smaller ones.
+    pub fn split_off(&mut self, val: &T) -> Self {
+        let (lower, upper) = BSTNode::split(self.root.take(), |x| x < val);
+        self.root = lower;
+        Self { root: upper }
+    }
+
+    /// Elements in either tree. `other` is split around each node of `self`
+    /// and the pieces are joined back together, so the result keeps the
+    /// shape of `self` rather than being rebalanced.
+    pub fn union(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Union);
+        Self { root }
+    }
+
+    /// Elements in both trees, combined the same way as
+    /// [`union`](Self::union).
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Intersection);
+        Self { root }
+    }
+
+    /// Elements of `self` that are not in `other`, combined the same way as
+    /// [`union`](Self::union).
+    pub fn difference(mut self, mut other: Self) -> Self {
+        let root = Self::combine(self.root.take(), other.root.take(), SetOp::Difference);
+        Self { root }
+    }
+
+    // Join-based set operation: split `b` around the root of `a`, combine
+    // the halves and join the results back together. Elements equal to the
+    // root are gathered from both trees and resolved by `op`. The recursion
+    // runs on an explicit stack, since `a` may be a long path.
+    fn combine(a: Link<T>, b: Link<T>, op: SetOp) -> Link<T> {
+        enum Task<T> {
+            Combine(Link<T>, Link<T>),
+            // Joins the two topmost results around these equal elements.
+            Join(Link<T>),
+        }
+
+        let mut tasks = vec![Task::Combine(a, b)];
+        let mut results = Vec::new();
+        while let Some(task) = tasks.pop() {
+            let (mut root, b) = match task {
+                Task::Join(mid) => {
+                    let upper = results.pop().unwrap();
+                    let lower = results.pop().unwrap();
+                    results.push(BSTNode::join(BSTNode::join(lower, mid), upper));
+                    continue;
+                }
+                Task::Combine(None, b) => {
+                    let keep = matches!(op, SetOp::Union | SetOp::Append);
+                    results.push(Self::keep_or_drop(b, keep));
+                    continue;
+                }
+                Task::Combine(a, None) => {
+                    results.push(Self::keep_or_drop(a, op != SetOp::Intersection));
+                    continue;
+                }
+                Task::Combine(Some(root), b) => (root, b),
+            };
+
+            let pivot = &root.data;
+            let (a_lt, a_eq_left) = BSTNode::split(root.left.take(), |x| x < pivot);
+            let (a_eq_right, a_gt) = BSTNode::split(root.right.take(), |x| x <= pivot);
+            let (b_lt, rest) = BSTNode::split(b, |x| x < pivot);
+            let (b_eq, b_gt) = BSTNode::split(rest, |x| x <= pivot);
+            root.update_size();
+
+            let a_eq = BSTNode::join(BSTNode::join(a_eq_left, Some(root)), a_eq_right);
+            let (a_count, b_count) = (BSTNode::size(&a_eq), BSTNode::size(&b_eq));
+            let mid = match op {
+                SetOp::Union | SetOp::Intersection => {
+                    let a_wins = (a_count >= b_count) == (op == SetOp::Union);
+                    let (kept, dropped) = if a_wins { (a_eq, b_eq) } else { (b_eq, a_eq) };
+                    BSTNode::drop_all(dropped);
+                    kept
+                }
+                SetOp::Difference => {
+                    let mut rest = a_eq;
+                    for _ in 0..b_count {
+                        BSTNode::take_min(&mut rest);
+                    }
+                    BSTNode::drop_all(b_eq);
+                    rest
+                }
+                SetOp::Append => BSTNode::join(a_eq, b_eq),
+            };
+
+            tasks.push(Task::Join(mid));
+            tasks.push(Task::Combine(a_gt, b_gt));
+            tasks.push(Task::Combine(a_lt, b_lt));
+        }
+        results.pop().unwrap()
+    }
+
+    fn keep_or_drop(link: Link<T>, keep: bool) -> Link<T> {
+        if keep {
+            link
         } else {
-            match borrowed_val.right {
-                Some(ref right) => Self::insert_node(right, node),
-                None => borrowed_val.right = Some(node),
+            BSTNode::drop_all(link);
+            None
+        }
+    }
+
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T> {
+        let mut front = Vec::new();
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            if above_start(&node.data, range.start_bound()) {
+                front.push(node);
+                link = &node.left;
+            } else {
+                link = &node.right;
             }
         }
+
+        let mut back = Vec::new();
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            if below_end(&node.data, range.end_bound()) {
+                back.push(node);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+
+        let mut range = Range { front, back };
+        let is_empty = match (range.front.last(), range.back.last()) {
+            (Some(first), Some(last)) => first.data > last.data,
+            _ => true,
+        };
+        if is_empty {
+            range.front.clear();
+            range.back.clear();
+        }
+        range
     }
+}
 
-    pub fn search(&self, val: T) -> bool {
-        Self::search_node(&self.root, val)
-    }
-
-    fn search_node(curr: &Link<T>, val: T) -> bool {
-        match curr {
-            Some(node) => {
-                if val < node.borrow().data {
-                    Self::search_node(&node.borrow().left, val)
-                } else if val > node.borrow().data {
-                    Self::search_node(&node.borrow().right, val)
-                } else {
-                    true
+/// Counts the elements inside `range` given `count_less(val, inclusive)`,
+/// the number of elements below (or, if `inclusive`, at or below) `val`.
+pub(crate) fn count_in_range<T, R: RangeBounds<T>>(
+    range: &R,
+    len: usize,
+    count_less: impl Fn(&T, bool) -> usize,
+) -> usize {
+    let below_start = match range.start_bound() {
+        Bound::Included(start) => count_less(start, false),
+        Bound::Excluded(start) => count_less(start, true),
+        Bound::Unbounded => 0,
+    };
+    let up_to_end = match range.end_bound() {
+        Bound::Included(end) => count_less(end, true),
+        Bound::Excluded(end) => count_less(end, false),
+        Bound::Unbounded => len,
+    };
+    up_to_end.saturating_sub(below_start)
+}
+
+pub(crate) fn above_start<T: PartialOrd>(val: &T, start: Bound<&T>) -> bool {
+    match start {
+        Bound::Included(start) => val >= start,
+        Bound::Excluded(start) => val > start,
+        Bound::Unbounded => true,
+    }
+}
+
+pub(crate) fn below_end<T: PartialOrd>(val: &T, end: Bound<&T>) -> bool {
+    match end {
+        Bound::Included(end) => val <= end,
+        Bound::Excluded(end) => val < end,
+        Bound::Unbounded => true,
+    }
+}
+
+impl<T> Bst<T> {
+    pub fn len(&self) -> usize {
+        BSTNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, so an empty tree has height zero.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        BSTNode::depth_histogram(&self.root)
+    }
+
+    /// Mean number of nodes visited by a successful search.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    /// Returns the `k`-th smallest element, counting from zero.
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            let left_size = BSTNode::size(&node.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => link = &node.left,
+                Ordering::Equal => return Some(&node.data),
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    link = &node.right;
                 }
             }
-            None => false,
         }
+        None
     }
 
-    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
-        Self::inorder(&self.root, visit_fn);
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            front: Vec::new(),
+            back: Vec::new(),
+            remaining: self.len(),
+        };
+        Iter::push_left(&mut iter.front, &self.root);
+        Iter::push_right(&mut iter.back, &self.root);
+        iter
     }
 
-    fn inorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
-        if let Some(ref n) = node {
-            Self::inorder(&n.borrow().left, visit_fn);
-            visit_fn(&n.borrow().data);
-            Self::inorder(&n.borrow().right, visit_fn);
+    pub fn preorder_iter(&self) -> PreorderIter<'_, T> {
+        PreorderIter {
+            stack: self.root.as_deref().into_iter().collect(),
         }
     }
 
-    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
-        Self::preorder(&self.root, visit_fn);
+    pub fn postorder_iter(&self) -> PostorderIter<'_, T> {
+        PostorderIter {
+            stack: self
+                .root
+                .as_deref()
+                .map(|n| (n, false))
+                .into_iter()
+                .collect(),
+        }
     }
 
-    fn preorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
-        if let Some(ref n) = node {
-            visit_fn(&n.borrow().data);
-            Self::preorder(&n.borrow().left, visit_fn);
-            Self::preorder(&n.borrow().right, visit_fn);
+    pub fn level_order_iter(&self) -> LevelOrderIter<'_, T> {
+        LevelOrderIter {
+            queue: self.root.as_deref().into_iter().collect(),
         }
     }
+}
 
-    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
-        Self::postorder(&self.root, visit_fn);
+pub struct Iter<'a, T> {
+    front: Vec<&'a BSTNode<T>>,
+    back: Vec<&'a BSTNode<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(stack: &mut Vec<&'a BSTNode<T>>, mut link: &'a Link<T>) {
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            stack.push(node);
+            link = &node.left;
+        }
+    }
+
+    fn push_right(stack: &mut Vec<&'a BSTNode<T>>, mut link: &'a Link<T>) {
+        while let Some(n) = link {
+            let node: &BSTNode<T> = n;
+            stack.push(node);
+            link = &node.right;
+        }
     }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
 
-    fn postorder(node: &Link<T>, visit_fn: &mut impl FnMut(&T)) {
-        if let Some(ref n) = node {
-            Self::postorder(&n.borrow().left, visit_fn);
-            Self::postorder(&n.borrow().right, visit_fn);
-            visit_fn(&n.borrow().data);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
         }
+
+        let node = self.front.pop()?;
+        Self::push_left(&mut self.front, &node.right);
+        self.remaining -= 1;
+        Some(&node.data)
     }
 
-    pub fn delete(&mut self, val: T) {
-        self.root = Self::delete_node(self.root.take(), val);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
+}
 
-    fn delete_node(node: Link<T>, val: T) -> Link<T> {
-        match node {
-            Some(ref n) => {
-                if val < n.borrow().data {
-                    let left = Rc::clone(n);
-                    let new_left = Self::delete_node(n.borrow().left.clone(), val);
-                    left.borrow_mut().left = new_left;
-                    Some(left)
-                } else if val > n.borrow().data {
-                    let right = Rc::clone(n);
-                    let new_right = Self::delete_node(n.borrow().right.clone(), val);
-                    right.borrow_mut().right = new_right;
-                    Some(right)
-                } else {
-                    if n.borrow().left.is_none() {
-                        return n.borrow().right.clone();
-                    } else if n.borrow().right.is_none() {
-                        return n.borrow().left.clone();
-                    }
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
 
-                    let min = Self::find_min(n.borrow().right.as_ref().unwrap());
-                    let new_data = min.borrow().data.clone();
-                    let right = Rc::clone(n);
-                    let new_right = Self::delete_node(n.borrow().right.clone(), new_data.clone());
-                    right.borrow_mut().right = new_right;
-                    right.borrow_mut().data = new_data;
-                    Some(right)
-                }
+        let node = self.back.pop()?;
+        Self::push_right(&mut self.back, &node.left);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+pub struct PreorderIter<'a, T> {
+    stack: Vec<&'a BSTNode<T>>,
+}
+
+impl<'a, T> Iterator for PreorderIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if let Some(ref right) = node.right {
+            self.stack.push(right);
+        }
+        if let Some(ref left) = node.left {
+            self.stack.push(left);
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> FusedIterator for PreorderIter<'_, T> {}
+
+pub struct PostorderIter<'a, T> {
+    stack: Vec<(&'a BSTNode<T>, bool)>,
+}
+
+impl<'a, T> Iterator for PostorderIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(&node.data);
+            }
+
+            self.stack.push((node, true));
+            if let Some(ref right) = node.right {
+                self.stack.push((right, false));
+            }
+            if let Some(ref left) = node.left {
+                self.stack.push((left, false));
             }
-            None => None,
         }
     }
+}
+
+impl<T> FusedIterator for PostorderIter<'_, T> {}
+
+pub struct LevelOrderIter<'a, T> {
+    queue: VecDeque<&'a BSTNode<T>>,
+}
+
+impl<'a, T> Iterator for LevelOrderIter<'a, T> {
+    type Item = &'a T;
 
-    fn find_min(node: &Rc<RefCell<BSTNode<T>>>) -> Rc<RefCell<BSTNode<T>>> {
-        match node.borrow().left {
-            Some(ref left) => Self::find_min(left),
-            None => Rc::clone(node),
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        if let Some(ref left) = node.left {
+            self.queue.push_back(left);
+        }
+        if let Some(ref right) = node.right {
+            self.queue.push_back(right);
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> FusedIterator for LevelOrderIter<'_, T> {}
+
+pub struct Range<'a, T> {
+    front: Vec<&'a BSTNode<T>>,
+    back: Vec<&'a BSTNode<T>>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.front.pop()?;
+        // Both stacks point at the same node once a single element is left.
+        if self.back.last().is_some_and(|last| ptr::eq(*last, node)) {
+            self.front.clear();
+            self.back.clear();
+        } else {
+            Iter::push_left(&mut self.front, &node.right);
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> DoubleEndedIterator for Range<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.back.pop()?;
+        if self.front.last().is_some_and(|first| ptr::eq(*first, node)) {
+            self.front.clear();
+            self.back.clear();
+        } else {
+            Iter::push_right(&mut self.back, &node.left);
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> FusedIterator for Range<'_, T> {}
+
+pub struct IntoIter<T> {
+    stack: Vec<Box<BSTNode<T>>>,
+    remaining: usize,
+}
+
+impl<T> IntoIter<T> {
+    fn push_left(&mut self, mut link: Link<T>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        self.remaining -= 1;
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for node in self.stack.drain(..) {
+            BSTNode::drop_all(Some(node));
+        }
+    }
+}
+
+impl<T> IntoIterator for Bst<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Bst<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: fmt::Display> Bst<T> {
+    /// Renders the tree as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &BSTNode<T>| n.data.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            BSTNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Bst<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &BSTNode<T>| n.data.to_string();
+        render::write_ascii(f, self.root.as_deref(), BSTNode::children, label)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Bst<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Drop for Bst<T> {
+    fn drop(&mut self) {
+        BSTNode::drop_all(self.root.take());
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for Bst<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for Bst<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Bst;
+
+    // Serialized as a sequence in ascending order, which deserializes into
+    // a balanced tree.
+    impl<T: Serialize> Serialize for Bst<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T: Deserialize<'de> + PartialOrd> Deserialize<'de> for Bst<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let items = Vec::<T>::deserialize(deserializer)?;
+            if !items.windows(2).all(|w| w[0] <= w[1]) {
+                return Err(de::Error::custom("elements are not in ascending order"));
+            }
+            Ok(Self::from_sorted_iter(items))
         }
     }
 }
@@ -155,6 +1084,7 @@ impl<T: PartialOrd + Clone> Bst<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{binary_trees::Violation, test_util::Lcg};
 
     #[test]
     fn test_insert_and_search() {
@@ -293,4 +1223,595 @@ mod tests {
         assert!(tree.search(3));
         assert!(tree.search(7));
     }
+
+    #[test]
+    fn test_delete_two_children_without_clone() {
+        #[derive(Debug, PartialEq, PartialOrd)]
+        struct NoClone(u32);
+
+        let mut tree = Bst::new();
+        for x in [5, 3, 8, 7, 9] {
+            tree.insert(NoClone(x));
+        }
+
+        tree.delete(NoClone(5));
+        tree.delete(NoClone(8));
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.pop_min(), Some(NoClone(3)));
+        assert_eq!(tree.pop_min(), Some(NoClone(7)));
+        assert_eq!(tree.pop_min(), Some(NoClone(9)));
+    }
+
+    fn sorted_inserts(n: u32) -> Bst<u32> {
+        let mut tree = Bst::new();
+        for k in 0..n {
+            tree.insert(k);
+        }
+        tree
+    }
+
+    // Builds the right-leaning chain that inserting `0..n` in order produces.
+    // Doing the inserts themselves would take quadratic time at this scale.
+    fn sorted_chain(n: u32) -> Bst<u32> {
+        let mut root = None;
+        for (i, data) in (0..n).rev().enumerate() {
+            root = Some(Box::new(BSTNode {
+                left: None,
+                right: root,
+                data,
+                size: i + 1,
+            }));
+        }
+        Bst { root }
+    }
+
+    #[test]
+    fn test_sorted_chain_matches_sorted_inserts() {
+        let inserted = sorted_inserts(2000);
+        let chain = sorted_chain(2000);
+        assert!(inserted.preorder_iter().eq(chain.preorder_iter()));
+        assert_eq!(check_sizes_u32(&chain.root), 2000);
+    }
+
+    fn check_sizes_u32(link: &Link<u32>) -> usize {
+        let mut total = 0;
+        let mut link = link;
+        while let Some(n) = link {
+            assert_eq!(n.size, 1 + BSTNode::size(&n.left) + BSTNode::size(&n.right));
+            total += 1;
+            link = &n.right;
+        }
+        total
+    }
+
+    // Runs every operation on the path that inserting `0..n` in order leaves
+    // behind, which would overflow the stack if any of them recursed.
+    fn check_sorted_path(mut tree: Bst<u32>, n: u32) {
+        assert_eq!(tree.len(), n as usize);
+        assert_eq!(tree.height(), n as usize);
+
+        tree.insert(n);
+        assert!(tree.search(n));
+        assert!(!tree.search(n + 1));
+        assert_eq!(tree.max(), Some(&n));
+        assert_eq!(tree.select(n as usize - 1), Some(&(n - 1)));
+        assert_eq!(tree.rank(&n), n as usize);
+        assert_eq!(tree.floor(&(n + 5)), Some(&n));
+
+        tree.delete(n - 1);
+        tree.delete(0);
+        assert_eq!(tree.pop_max(), Some(n));
+        assert_eq!(tree.pop_min(), Some(1));
+        assert_eq!(tree.len(), n as usize - 3);
+
+        let mut count = 0;
+        tree.inorder_traversal(&mut |_| count += 1);
+        tree.preorder_traversal(&mut |_| count += 1);
+        tree.postorder_traversal(&mut |_| count += 1);
+        assert_eq!(count, 3 * tree.len());
+        assert_eq!(tree.iter().next_back(), Some(&(n - 2)));
+        assert_eq!(tree.range(n - 4..).count(), 3);
+        assert_eq!(tree.validate(), Ok(()));
+
+        let upper = tree.split_off(&(n / 2));
+        assert_eq!(upper.len(), (n - 1 - n / 2) as usize);
+        drop(upper);
+
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.next(), Some(2));
+        drop(iter);
+    }
+
+    #[test]
+    fn test_sorted_inserts() {
+        check_sorted_path(sorted_inserts(20_000), 20_000);
+    }
+
+    #[test]
+    fn test_million_key_sorted_chain() {
+        check_sorted_path(sorted_chain(1_000_000), 1_000_000);
+    }
+
+    // Each sorted insert walks the whole path, so this takes quadratic time;
+    // run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_ten_million_sorted_keys() {
+        const N: u32 = 10_000_000;
+        check_sorted_path(sorted_inserts(N), N);
+    }
+
+    fn sample_tree() -> Bst<i32> {
+        [5, 3, 7, 2, 4, 6, 8].into_iter().collect()
+    }
+
+    #[test]
+    fn test_iter() {
+        let tree = sample_tree();
+        let elements: Vec<i32> = tree.iter().copied().collect();
+        assert_eq!(elements, vec![2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(tree.iter().len(), 7);
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let tree = sample_tree();
+        let elements: Vec<i32> = tree.iter().rev().copied().collect();
+        assert_eq!(elements, vec![8, 7, 6, 5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn test_iter_double_ended_meets_in_middle() {
+        let tree = sample_tree();
+        let mut iter = tree.iter();
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&8));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next_back(), Some(&7));
+        assert_eq!(iter.next_back(), Some(&6));
+        assert_eq!(iter.next(), Some(&4));
+        assert_eq!(iter.next(), Some(&5));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_iter_early_exit() {
+        let tree = sample_tree();
+        let mut seen = vec![];
+        for x in &tree {
+            if *x > 4 {
+                break;
+            }
+            seen.push(*x);
+        }
+        assert_eq!(seen, vec![2, 3, 4]);
+
+        let zipped: Vec<(i32, i32)> = tree
+            .iter()
+            .zip(tree.iter().rev())
+            .take(2)
+            .map(|(a, b)| (*a, *b))
+            .collect();
+        assert_eq!(zipped, vec![(2, 8), (3, 7)]);
+    }
+
+    #[test]
+    fn test_preorder_iter() {
+        let tree = sample_tree();
+        let elements: Vec<i32> = tree.preorder_iter().copied().collect();
+        assert_eq!(elements, vec![5, 3, 2, 4, 7, 6, 8]);
+    }
+
+    #[test]
+    fn test_postorder_iter() {
+        let tree = sample_tree();
+        let elements: Vec<i32> = tree.postorder_iter().copied().collect();
+        assert_eq!(elements, vec![2, 4, 3, 6, 8, 7, 5]);
+    }
+
+    #[test]
+    fn test_level_order_iter() {
+        let tree = sample_tree();
+        let elements: Vec<i32> = tree.level_order_iter().copied().collect();
+        assert_eq!(elements, vec![5, 3, 7, 2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_empty_iters() {
+        let tree: Bst<i32> = Bst::new();
+        assert_eq!(tree.iter().next(), None);
+        assert_eq!(tree.iter().next_back(), None);
+        assert_eq!(tree.preorder_iter().next(), None);
+        assert_eq!(tree.postorder_iter().next(), None);
+        assert_eq!(tree.level_order_iter().next(), None);
+        assert_eq!(tree.into_iter().next(), None);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let tree: Bst<String> = ["b", "a", "c"].into_iter().map(String::from).collect();
+        let elements: Vec<String> = tree.into_iter().collect();
+        assert_eq!(elements, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_into_iter_partial_drop() {
+        let tree = sample_tree();
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.len(), 6);
+    }
+
+    #[test]
+    fn test_extend_and_len() {
+        let mut tree = sample_tree();
+        tree.extend([1, 9]);
+        assert_eq!(tree.len(), 9);
+        tree.delete(5);
+        tree.delete(42);
+        assert_eq!(tree.len(), 8);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 6, 7, 8, 9]
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        let tree = sample_tree();
+        assert_eq!(tree.min(), Some(&2));
+        assert_eq!(tree.max(), Some(&8));
+
+        let empty: Bst<i32> = Bst::new();
+        assert_eq!(empty.min(), None);
+        assert_eq!(empty.max(), None);
+    }
+
+    #[test]
+    fn test_floor_and_ceiling() {
+        let tree: Bst<i32> = [50, 20, 80, 10, 30, 70, 90].into_iter().collect();
+        assert_eq!(tree.floor(&30), Some(&30));
+        assert_eq!(tree.floor(&35), Some(&30));
+        assert_eq!(tree.floor(&65), Some(&50));
+        assert_eq!(tree.floor(&5), None);
+        assert_eq!(tree.ceiling(&30), Some(&30));
+        assert_eq!(tree.ceiling(&35), Some(&50));
+        assert_eq!(tree.ceiling(&85), Some(&90));
+        assert_eq!(tree.ceiling(&95), None);
+    }
+
+    #[test]
+    fn test_predecessor_and_successor() {
+        let tree: Bst<i32> = [50, 20, 80, 10, 30, 70, 90].into_iter().collect();
+        assert_eq!(tree.predecessor(&50), Some(&30));
+        assert_eq!(tree.predecessor(&70), Some(&50));
+        assert_eq!(tree.predecessor(&10), None);
+        assert_eq!(tree.successor(&30), Some(&50));
+        assert_eq!(tree.successor(&50), Some(&70));
+        assert_eq!(tree.successor(&90), None);
+        assert_eq!(tree.successor(&55), Some(&70));
+    }
+
+    #[test]
+    fn test_pop_min_and_max() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.pop_min(), Some(2));
+        assert_eq!(tree.pop_max(), Some(8));
+        assert_eq!(tree.pop_min(), Some(3));
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), vec![4, 5, 6, 7]);
+
+        let mut drained = vec![];
+        while let Some(x) = tree.pop_max() {
+            drained.push(x);
+        }
+        assert_eq!(drained, vec![7, 6, 5, 4]);
+        assert!(tree.is_empty());
+        assert_eq!(tree.pop_min(), None);
+    }
+
+    #[test]
+    fn test_range() {
+        let tree: Bst<i32> = (0..20).map(|x| (x * 7) % 20).collect();
+        let collect = |r: Range<'_, i32>| r.copied().collect::<Vec<_>>();
+
+        assert_eq!(collect(tree.range(5..9)), vec![5, 6, 7, 8]);
+        assert_eq!(collect(tree.range(5..=9)), vec![5, 6, 7, 8, 9]);
+        assert_eq!(collect(tree.range(..3)), vec![0, 1, 2]);
+        assert_eq!(collect(tree.range(17..)), vec![17, 18, 19]);
+        assert_eq!(collect(tree.range(..)), (0..20).collect::<Vec<_>>());
+        assert_eq!(
+            collect(tree.range((Bound::Excluded(4), Bound::Excluded(7)))),
+            vec![5, 6]
+        );
+        assert!(collect(tree.range((Bound::Included(9), Bound::Excluded(5)))).is_empty());
+        assert!(collect(tree.range(25..30)).is_empty());
+        assert!(collect(tree.range(6..6)).is_empty());
+    }
+
+    #[test]
+    fn test_range_double_ended() {
+        let tree: Bst<i32> = (0..20).map(|x| (x * 7) % 20).collect();
+        let rev: Vec<i32> = tree.range(3..8).rev().copied().collect();
+        assert_eq!(rev, vec![7, 6, 5, 4, 3]);
+
+        let mut range = tree.range(3..8);
+        assert_eq!(range.next(), Some(&3));
+        assert_eq!(range.next_back(), Some(&7));
+        assert_eq!(range.next_back(), Some(&6));
+        assert_eq!(range.next(), Some(&4));
+        assert_eq!(range.next(), Some(&5));
+        assert_eq!(range.next(), None);
+        assert_eq!(range.next_back(), None);
+    }
+
+    #[test]
+    fn test_range_with_duplicates() {
+        let tree: Bst<i32> = [5, 5, 3, 5, 7].into_iter().collect();
+        assert_eq!(tree.range(5..=5).count(), 3);
+        assert_eq!(tree.range(4..6).rev().count(), 3);
+    }
+
+    fn check_sizes(link: &Link<i32>) -> usize {
+        match link {
+            Some(n) => {
+                let size = 1 + check_sizes(&n.left) + check_sizes(&n.right);
+                assert_eq!(n.size, size);
+                size
+            }
+            None => 0,
+        }
+    }
+
+    #[test]
+    fn test_select_and_rank() {
+        let tree: Bst<i32> = [50, 20, 80, 10, 30, 70, 90].into_iter().collect();
+        let sorted = [10, 20, 30, 50, 70, 80, 90];
+        for (k, val) in sorted.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(val));
+            assert_eq!(tree.rank(val), k);
+        }
+        assert_eq!(tree.select(7), None);
+        assert_eq!(tree.rank(&0), 0);
+        assert_eq!(tree.rank(&55), 4);
+        assert_eq!(tree.rank(&100), 7);
+    }
+
+    #[test]
+    fn test_count_range() {
+        let tree: Bst<i32> = (0..20).map(|x| (x * 7) % 20).collect();
+        assert_eq!(tree.count_range(5..9), 4);
+        assert_eq!(tree.count_range(5..=9), 5);
+        assert_eq!(tree.count_range(..3), 3);
+        assert_eq!(tree.count_range(17..), 3);
+        assert_eq!(tree.count_range(..), 20);
+        assert_eq!(
+            tree.count_range((Bound::Excluded(4), Bound::Excluded(7))),
+            2
+        );
+        assert_eq!(
+            tree.count_range((Bound::Included(9), Bound::Excluded(5))),
+            0
+        );
+        assert_eq!(tree.count_range(25..30), 0);
+    }
+
+    #[test]
+    fn test_sizes_maintained() {
+        let mut tree: Bst<i32> = (0..64).map(|x| (x * 37) % 64).collect();
+        assert_eq!(check_sizes(&tree.root), 64);
+        for x in (0..64).step_by(3) {
+            tree.delete(x);
+            check_sizes(&tree.root);
+        }
+        tree.delete(1000);
+        tree.pop_min();
+        tree.pop_max();
+        assert_eq!(check_sizes(&tree.root), tree.len());
+        assert_eq!(tree.len(), 64 - 22 - 2);
+        for k in 0..tree.len() {
+            assert_eq!(tree.rank(tree.select(k).unwrap()), k);
+        }
+    }
+
+    #[test]
+    fn test_from_sorted_iter() {
+        let tree = Bst::from_sorted_iter(0..1023);
+        assert_eq!(check_sizes(&tree.root), 1023);
+        assert_eq!(tree.preorder_iter().next(), Some(&511));
+        assert!(tree.iter().copied().eq(0..1023));
+
+        let mut depth = 0;
+        let mut link = &tree.root;
+        while let Some(n) = link {
+            depth += 1;
+            link = &n.left;
+        }
+        assert_eq!(depth, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ascending order")]
+    fn test_from_sorted_iter_rejects_unsorted() {
+        Bst::from_sorted_iter([2, 1]);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut tree: Bst<i32> = [5, 3, 7, 2, 4, 6, 8, 5, 5].into_iter().collect();
+        let upper = tree.split_off(&5);
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(
+            upper.iter().copied().collect::<Vec<_>>(),
+            vec![5, 5, 5, 6, 7, 8]
+        );
+        check_sizes(&tree.root);
+        check_sizes(&upper.root);
+        assert_eq!(upper.rank(&7), 4);
+
+        let mut all = sample_tree();
+        assert!(all.split_off(&0).iter().copied().eq(2..=8));
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    fn test_append() {
+        let mut tree = sample_tree();
+        let mut other: Bst<i32> = [10, 9, 11].into_iter().collect();
+        tree.append(&mut other);
+        assert!(other.is_empty());
+        assert!(tree.iter().copied().eq(2..=11));
+        assert_eq!(check_sizes(&tree.root), 10);
+
+        let mut overlapping: Bst<i32> = [1, 5, 12].into_iter().collect();
+        tree.append(&mut overlapping);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        check_sizes(&tree.root);
+
+        let mut empty = Bst::new();
+        empty.append(&mut tree);
+        assert_eq!(empty.len(), 13);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = || -> Bst<i32> { [1, 2, 2, 3, 5, 8].into_iter().collect() };
+        let b = || -> Bst<i32> { [2, 3, 3, 4, 8, 9].into_iter().collect() };
+        let items = |tree: Bst<i32>| {
+            check_sizes(&tree.root);
+            tree.into_iter().collect::<Vec<_>>()
+        };
+
+        assert_eq!(items(a().union(b())), vec![1, 2, 2, 3, 3, 4, 5, 8, 9]);
+        assert_eq!(items(a().intersection(b())), vec![2, 3, 8]);
+        assert_eq!(items(a().difference(b())), vec![1, 2, 5]);
+        assert_eq!(items(b().difference(a())), vec![3, 4, 9]);
+        assert!(a().intersection(Bst::new()).is_empty());
+    }
+
+    #[test]
+    fn test_set_operations_against_merge() {
+        let mut rng = Lcg(0x5e7);
+        for _ in 0..200 {
+            let a: Vec<u64> = (0..rng.next() % 60).map(|_| rng.next() % 40).collect();
+            let b: Vec<u64> = (0..rng.next() % 60).map(|_| rng.next() % 40).collect();
+            let tree = |items: &[u64]| -> Bst<u64> { items.iter().copied().collect() };
+            let sorted = |items: &[u64]| {
+                let mut items = items.to_vec();
+                items.sort_unstable();
+                items
+            };
+
+            for (op, combined) in [
+                (SetOp::Union, tree(&a).union(tree(&b))),
+                (SetOp::Intersection, tree(&a).intersection(tree(&b))),
+                (SetOp::Difference, tree(&a).difference(tree(&b))),
+            ] {
+                assert_eq!(combined.validate(), Ok(()));
+                let expected = merge_sorted(sorted(&a), sorted(&b), op);
+                assert_eq!(combined.into_iter().collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_operations_on_long_paths() {
+        const N: u32 = 200_000;
+        let balanced = || Bst::from_sorted_iter(N / 2..N + N / 2);
+
+        let union = sorted_chain(N).union(balanced());
+        assert_eq!(union.validate(), Ok(()));
+        assert!(union.iter().copied().eq(0..N + N / 2));
+
+        let intersection = sorted_chain(N).intersection(balanced());
+        assert!(intersection.iter().copied().eq(N / 2..N));
+
+        let difference = sorted_chain(N).difference(balanced());
+        assert!(difference.iter().copied().eq(0..N / 2));
+    }
+
+    #[test]
+    fn test_validate_catches_keys_changed_in_place() {
+        use std::cell::Cell;
+
+        let tree: Bst<Cell<i32>> = [5, 3, 7, 2, 4].into_iter().map(Cell::new).collect();
+        assert_eq!(tree.validate(), Ok(()));
+
+        // Node 4 sits at 5 -> 3 -> 4; pushing it past its grandparent breaks
+        // the order without touching the shape.
+        tree.iter().find(|c| c.get() == 4).unwrap().set(6);
+        let err = tree.validate().unwrap_err();
+        assert_eq!(err.path, "LR");
+        assert_eq!(err.violation, Violation::OutOfOrder);
+        assert_eq!(err.to_string(), "keys are out of order at node LR");
+    }
+
+    #[test]
+    fn test_validate_catches_stale_size() {
+        let mut tree = sample_tree();
+        tree.root.as_mut().unwrap().right.as_mut().unwrap().size = 5;
+        assert_eq!(
+            tree.validate(),
+            Err(InvariantError {
+                path: "R".to_string(),
+                violation: Violation::StaleSize {
+                    cached: 5,
+                    actual: 3
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_shape_statistics() {
+        let tree = sample_tree();
+        assert_eq!(tree.height(), 3);
+        assert_eq!(tree.depth_histogram(), vec![1, 2, 4]);
+        assert_eq!(tree.average_search_depth(), 17.0 / 7.0);
+
+        let chain: Bst<i32> = (0..100).collect();
+        assert_eq!(chain.height(), 100);
+        assert_eq!(chain.average_search_depth(), 50.5);
+        assert_eq!(chain.validate(), Ok(()));
+
+        let empty: Bst<i32> = Bst::new();
+        assert_eq!(empty.height(), 0);
+        assert!(empty.depth_histogram().is_empty());
+        assert_eq!(empty.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_render() {
+        let tree = Bst::from_sorted_iter([1, 2, 3]);
+        assert_eq!(tree.to_string(), "2\n|-- 1\n`-- 3\n");
+        assert!(tree
+            .to_dot_with(DotOptions::all())
+            .contains("n0 [label=\"2\\nh=2 b=0 n=3\"];"));
+
+        let mut chain = Bst::new();
+        chain.insert(1);
+        chain.insert(2);
+        assert_eq!(chain.to_string(), "1\n|-- nil\n`-- 2\n");
+        assert!(chain.to_dot().contains("nil0_0 [shape=point];"));
+        assert_eq!(Bst::<i32>::new().to_string(), "(empty)\n");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: Bst<i32> = (0..100).map(|i| (i * 37) % 100).chain([5, 5]).collect();
+        let json = serde_json::to_string(&tree).unwrap();
+
+        let back: Bst<i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(tree.iter()));
+        assert_eq!(back.height(), 7);
+        assert_eq!(back.validate(), Ok(()));
+        assert!(serde_json::from_str::<Bst<i32>>("[2,1]").is_err());
+    }
 }
@@ -0,0 +1,479 @@
+use std::{borrow::Borrow, fmt, mem};
+
+use super::{
+    bst::{BSTNode, Link},
+    render::{self, DotOptions},
+    validate::{self, InvariantError},
+};
+
+pub struct BstMap<K, V> {
+    root: Link<(K, V)>,
+}
+
+pub enum Entry<'a, K, V> {
+    Vacant(VacantEntry<'a, K, V>),
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    map: &'a mut BstMap<K, V>,
+}
+
+/// A present entry. It keeps the probe key and looks the node up again on
+/// each access, so the map can own its nodes uniquely.
+pub struct OccupiedEntry<'a, K, V> {
+    key: K,
+    map: &'a mut BstMap<K, V>,
+}
+
+impl<K: Ord, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        BSTNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.get_mut(&key) {
+            Some(old) => Some(mem::replace(old, value)),
+            None => {
+                BSTNode::insert(&mut self.root, (key, value), &mut |a, b| a.0.cmp(&b.0));
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        BSTNode::find(&self.root, &mut |(k, _)| key.cmp(k.borrow())).map(|node| &node.data.1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        BSTNode::find_mut(&mut self.root, &mut |(k, _)| key.cmp(k.borrow()))
+            .map(|node| &mut node.data.1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        BSTNode::remove(&mut self.root, &mut |(k, _)| key.cmp(k.borrow()))
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        if self.contains_key(&key) {
+            Entry::Occupied(OccupiedEntry { key, map: self })
+        } else {
+            Entry::Vacant(VacantEntry { key, map: self })
+        }
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        BSTNode::inorder(&self.root, &mut |(k, v)| visit_fn(k, v));
+    }
+
+    /// Checks that keys are strictly ordered and that the cached subtree
+    /// sizes are correct.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        BSTNode::validate(&self.root, false, |(k, _)| k)
+    }
+
+    /// Height counted in nodes, so an empty map has height zero.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        BSTNode::depth_histogram(&self.root)
+    }
+
+    /// Mean number of nodes visited by a successful lookup.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for BstMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        BSTNode::inorder(&self.root, &mut |(k, v)| {
+            map.entry(k, v);
+        });
+        map.finish()
+    }
+}
+
+impl<K: fmt::Display, V> BstMap<K, V> {
+    /// Renders the map's keys as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &BSTNode<(K, V)>| n.data.0.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            BSTNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> fmt::Display for BstMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &BSTNode<(K, V)>| n.data.0.to_string();
+        render::write_ascii(f, self.root.as_deref(), BSTNode::children, label)
+    }
+}
+
+impl<K, V> Drop for BstMap<K, V> {
+    fn drop(&mut self) {
+        BSTNode::drop_all(self.root.take());
+    }
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Vacant(entry) => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K: Ord, V: Default> Entry<'a, K, V> {
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let (_, value) = BSTNode::insert(&mut self.map.root, (self.key, value), &mut |a, b| {
+            a.0.cmp(&b.0)
+        });
+        value
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get(&self) -> &V {
+        self.map.get(&self.key).unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.get_mut(&self.key).unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.get_mut(&self.key).unwrap()
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove(&self.key).unwrap()
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{BSTNode, BstMap};
+
+    // Serialized as a sequence of `(key, value)` pairs in key order, so keys
+    // need not be strings in formats such as JSON.
+    impl<K: Serialize + Ord, V: Serialize> Serialize for BstMap<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.len()))?;
+            let mut result = Ok(());
+            self.inorder_traversal(&mut |k, v| {
+                if result.is_ok() {
+                    result = seq.serialize_element(&(k, v));
+                }
+            });
+            result?;
+            seq.end()
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for BstMap<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+            if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+                return Err(de::Error::custom(
+                    "keys are not in strictly ascending order",
+                ));
+            }
+            let len = entries.len();
+            Ok(Self {
+                root: BSTNode::build(&mut entries.into_iter(), len),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(map: &BstMap<i32, &'static str>) -> Vec<(i32, &'static str)> {
+        let mut elements = vec![];
+        map.inorder_traversal(&mut |k, v| elements.push((*k, *v)));
+        elements
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut map = BstMap::new();
+        assert_eq!(map.insert(5, "five"), None);
+        assert_eq!(map.insert(3, "three"), None);
+        assert_eq!(map.insert(7, "seven"), None);
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&5), Some(&"five"));
+        assert_eq!(map.get(&3), Some(&"three"));
+        assert_eq!(map.get(&7), Some(&"seven"));
+        assert_eq!(map.get(&4), None);
+    }
+
+    #[test]
+    fn test_insert_returns_old_value() {
+        let mut map = BstMap::new();
+        map.insert(1, "a");
+        assert_eq!(map.insert(1, "b"), Some("a"));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), Some(&"b"));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut map = BstMap::new();
+        map.insert(1, 10);
+        if let Some(v) = map.get_mut(&1) {
+            *v += 5;
+        }
+        assert_eq!(map.get(&1), Some(&15));
+        assert_eq!(map.get_mut(&2), None);
+    }
+
+    #[test]
+    fn test_contains_key_with_borrowed_key() {
+        let mut map = BstMap::new();
+        map.insert(String::from("apple"), 1);
+        map.insert(String::from("pear"), 2);
+
+        assert!(map.contains_key("apple"));
+        assert!(!map.contains_key("plum"));
+        assert_eq!(map.get("pear"), Some(&2));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut map = BstMap::new();
+        for (k, v) in [(5, "e"), (3, "c"), (7, "g"), (6, "f"), (8, "h")] {
+            map.insert(k, v);
+        }
+
+        assert_eq!(map.remove(&7), Some((7, "g")));
+        assert_eq!(map.remove(&7), None);
+        assert_eq!(map.remove(&5), Some((5, "e")));
+        assert_eq!(map.len(), 3);
+        assert_eq!(entries(&map), vec![(3, "c"), (6, "f"), (8, "h")]);
+    }
+
+    #[test]
+    fn test_remove_without_clone() {
+        struct NoClone(u32);
+
+        let mut map = BstMap::new();
+        for k in [2, 1, 3] {
+            map.insert(k, NoClone(k * 10));
+        }
+
+        let (k, v) = map.remove(&2).unwrap();
+        assert_eq!((k, v.0), (2, 20));
+        assert_eq!(map.get(&3).map(|v| v.0), Some(30));
+    }
+
+    #[test]
+    fn test_entry_or_insert() {
+        let mut map = BstMap::new();
+        *map.entry("a").or_insert(0) += 1;
+        *map.entry("a").or_insert(0) += 1;
+        *map.entry("b").or_insert(0) += 1;
+
+        assert_eq!(map.get("a"), Some(&2));
+        assert_eq!(map.get("b"), Some(&1));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_entry_or_insert_with() {
+        let mut map: BstMap<i32, Vec<i32>> = BstMap::new();
+        map.entry(1).or_insert_with(|| vec![0]).push(1);
+        map.entry(1).or_insert_with(|| unreachable!()).push(2);
+
+        assert_eq!(map.get(&1), Some(&vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn test_entry_and_modify() {
+        let mut map = BstMap::new();
+        map.entry("x").and_modify(|v| *v += 1).or_insert(42);
+        assert_eq!(map.get("x"), Some(&42));
+
+        map.entry("x").and_modify(|v| *v += 1).or_insert(42);
+        assert_eq!(map.get("x"), Some(&43));
+    }
+
+    #[test]
+    fn test_entry_or_default() {
+        let mut map: BstMap<&str, u32> = BstMap::new();
+        *map.entry("hits").or_default() += 3;
+        assert_eq!(map.get("hits"), Some(&3));
+    }
+
+    #[test]
+    fn test_occupied_entry() {
+        let mut map = BstMap::new();
+        for k in [4, 2, 6, 1, 3, 5, 7] {
+            map.insert(k, k * 100);
+        }
+
+        match map.entry(4) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &4);
+                assert_eq!(entry.get(), &400);
+                assert_eq!(entry.insert(401), 400);
+                assert_eq!(entry.remove_entry(), (4, 401));
+            }
+            Entry::Vacant(_) => panic!("expected occupied entry"),
+        }
+
+        assert!(!map.contains_key(&4));
+        assert_eq!(map.len(), 6);
+        match map.entry(4) {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 4),
+            Entry::Occupied(_) => panic!("expected vacant entry"),
+        }
+    }
+
+    #[test]
+    fn test_validate_and_statistics() {
+        let mut map = BstMap::new();
+        for k in [4, 2, 6, 1, 3, 5, 7] {
+            map.insert(k, k);
+        }
+        map.remove(&4);
+        assert_eq!(map.validate(), Ok(()));
+        assert_eq!(map.height(), 3);
+        assert_eq!(map.depth_histogram(), vec![1, 2, 3]);
+        assert_eq!(map.average_search_depth(), 14.0 / 6.0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut map = BstMap::new();
+        map.insert(2, "two".to_string());
+        map.insert(1, "one".to_string());
+        map.insert(3, "three".to_string());
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"[[1,"one"],[2,"two"],[3,"three"]]"#);
+
+        let back: BstMap<i32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 3);
+        assert_eq!(back.get(&2).map(String::as_str), Some("two"));
+        assert_eq!(back.validate(), Ok(()));
+
+        let keys: Vec<(u32, u32)> = (0..1000).map(|k| (k, k)).collect();
+        let json = serde_json::to_string(&keys).unwrap();
+        let back: BstMap<u32, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.height(), 10);
+        assert_eq!(back.validate(), Ok(()));
+
+        assert!(serde_json::from_str::<BstMap<i32, i32>>("[[2,0],[1,0]]").is_err());
+        assert!(serde_json::from_str::<BstMap<i32, i32>>("[[1,0],[1,0]]").is_err());
+    }
+}
@@ -0,0 +1,583 @@
+use std::ops::{AddAssign, RangeBounds, Sub};
+
+use crate::{arrays::DynamicArray, util::index_bounds};
+
+type Nodes<T> = DynamicArray<T, 16>;
+
+/// A Fenwick (binary indexed) tree: point updates and prefix sums in
+/// `O(log n)`, with `T::default()` as zero.
+///
+/// Node `i` (counting from one) holds the sum of the `i & i.wrapping_neg()`
+/// elements ending at element `i`.
+#[derive(Debug)]
+pub struct FenwickTree<T> {
+    nodes: Nodes<T>,
+}
+
+impl<T> FenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    /// Creates a tree of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        std::iter::repeat_n(T::default(), len).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Adds `delta` to the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn add(&mut self, index: usize, delta: T) {
+        assert!(index < self.len(), "index out of bounds");
+        let mut i = index + 1;
+        while i <= self.len() {
+            *self.nodes.get_mut(i - 1).unwrap() += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of the first `end` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn prefix_sum(&self, end: usize) -> T {
+        assert!(end <= self.len(), "range end out of bounds");
+        let mut sum = T::default();
+        let mut i = end;
+        while i > 0 {
+            sum += self.node(i);
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn sum<R: RangeBounds<usize>>(&self, range: R) -> T {
+        let (start, end) = index_bounds(range, self.len());
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        self.sum(index..=index)
+    }
+
+    pub fn set(&mut self, index: usize, value: T) {
+        let delta = value - self.get(index);
+        self.add(index, delta);
+    }
+
+    fn node(&self, i: usize) -> T {
+        *self.nodes.get(i - 1).unwrap()
+    }
+}
+
+impl<T> FenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T> + PartialOrd,
+{
+    /// Returns the smallest `end` with `prefix_sum(end) >= target`, or `None`
+    /// if the whole tree sums to less. Every element must be non-negative,
+    /// so that prefix sums only grow.
+    pub fn lower_bound(&self, target: T) -> Option<usize> {
+        if target <= T::default() {
+            return Some(0);
+        }
+
+        // Walk down the implicit tree, keeping `pos` as the longest prefix
+        // known to fall short of the target.
+        let mut pos = 0;
+        let mut remaining = target;
+        let mut step = (self.len() + 1).next_power_of_two() / 2;
+        while step > 0 {
+            if pos + step <= self.len() && self.node(pos + step) < remaining {
+                pos += step;
+                remaining = remaining - self.node(pos);
+            }
+            step /= 2;
+        }
+        (pos < self.len()).then_some(pos + 1)
+    }
+}
+
+/// Builds the tree in linear time by passing each node's total on to its
+/// parent.
+impl<T> FromIterator<T> for FenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut nodes = Nodes::new();
+        for value in iter {
+            nodes.push(value);
+        }
+
+        let len = nodes.len();
+        for i in 1..=len {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                let total = *nodes.get(i - 1).unwrap();
+                *nodes.get_mut(parent - 1).unwrap() += total;
+            }
+        }
+        Self { nodes }
+    }
+}
+
+/// Adds `value` to itself `n` times in `O(log n)` additions.
+fn times<T: Copy + Default + AddAssign>(mut value: T, mut n: usize) -> T {
+    let mut total = T::default();
+    while n > 0 {
+        if n & 1 == 1 {
+            total += value;
+        }
+        let double = value;
+        value += double;
+        n >>= 1;
+    }
+    total
+}
+
+/// A Fenwick tree that adds a constant to a whole range and sums ranges,
+/// both in `O(log n)`.
+///
+/// It keeps two trees over the differences between neighbouring elements,
+/// so `T` must represent negative values, as signed integers and floats do.
+#[derive(Debug)]
+pub struct RangeFenwickTree<T> {
+    // Prefix sums of `diffs` give the elements themselves; `weighted` holds
+    // each difference times its index, which corrects the prefix sums of
+    // the elements.
+    diffs: FenwickTree<T>,
+    weighted: FenwickTree<T>,
+}
+
+impl<T> RangeFenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    /// Creates a tree of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        Self {
+            diffs: FenwickTree::new(len),
+            weighted: FenwickTree::new(len),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.diffs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diffs.is_empty()
+    }
+
+    /// Adds `delta` to every element in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn add<R: RangeBounds<usize>>(&mut self, range: R, delta: T) {
+        let (start, end) = index_bounds(range, self.len());
+        if start == end {
+            return;
+        }
+
+        self.diffs.add(start, delta);
+        self.weighted.add(start, times(delta, start));
+        if end < self.len() {
+            self.diffs.add(end, T::default() - delta);
+            self.weighted.add(end, T::default() - times(delta, end));
+        }
+    }
+
+    /// Sum of the first `end` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn prefix_sum(&self, end: usize) -> T {
+        times(self.diffs.prefix_sum(end), end) - self.weighted.prefix_sum(end)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn sum<R: RangeBounds<usize>>(&self, range: R) -> T {
+        let (start, end) = index_bounds(range, self.len());
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len(), "index out of bounds");
+        self.diffs.prefix_sum(index + 1)
+    }
+}
+
+impl<T> FromIterator<T> for RangeFenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut previous = T::default();
+        let diffs: Vec<T> = iter
+            .into_iter()
+            .map(|value| {
+                let diff = value - previous;
+                previous = value;
+                diff
+            })
+            .collect();
+        let weighted = diffs.iter().enumerate().map(|(i, &d)| times(d, i));
+
+        Self {
+            weighted: weighted.collect(),
+            diffs: diffs.into_iter().collect(),
+        }
+    }
+}
+
+/// A two-dimensional Fenwick tree over a `rows` by `cols` grid: point
+/// updates and rectangle sums in `O(log rows * log cols)`.
+#[derive(Debug)]
+pub struct FenwickTree2D<T> {
+    rows: usize,
+    cols: usize,
+    // Row-major, `rows * cols` nodes.
+    nodes: Nodes<T>,
+}
+
+impl<T> FenwickTree2D<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    /// Creates a grid of zeros.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let mut nodes = Nodes::new();
+        for _ in 0..rows * cols {
+            nodes.push(T::default());
+        }
+        Self { rows, cols, nodes }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Adds `delta` to the cell at `(row, col)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is outside the grid.
+    pub fn add(&mut self, row: usize, col: usize, delta: T) {
+        assert!(row < self.rows && col < self.cols, "index out of bounds");
+        let mut i = row + 1;
+        while i <= self.rows {
+            let mut j = col + 1;
+            while j <= self.cols {
+                let at = (i - 1) * self.cols + (j - 1);
+                *self.nodes.get_mut(at).unwrap() += delta;
+                j += j & j.wrapping_neg();
+            }
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of the cells in the first `rows` rows and first `cols` columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either count exceeds the grid.
+    pub fn prefix_sum(&self, rows: usize, cols: usize) -> T {
+        assert!(
+            rows <= self.rows && cols <= self.cols,
+            "range end out of bounds"
+        );
+        let mut sum = T::default();
+        let mut i = rows;
+        while i > 0 {
+            let mut j = cols;
+            while j > 0 {
+                sum += *self.nodes.get((i - 1) * self.cols + (j - 1)).unwrap();
+                j &= j - 1;
+            }
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Sum of the cells in the given rows and columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either range is reversed or runs past the grid.
+    pub fn sum<R, C>(&self, rows: R, cols: C) -> T
+    where
+        R: RangeBounds<usize>,
+        C: RangeBounds<usize>,
+    {
+        let (top, bottom) = index_bounds(rows, self.rows);
+        let (left, right) = index_bounds(cols, self.cols);
+        let mut sum = self.prefix_sum(bottom, right);
+        sum += self.prefix_sum(top, left);
+        sum - self.prefix_sum(top, right) - self.prefix_sum(bottom, left)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> T {
+        self.sum(row..=row, col..=col)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::ops::{AddAssign, Sub};
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{FenwickTree, FenwickTree2D, RangeFenwickTree};
+
+    // All three trees serialize their element values rather than their
+    // partial sums; the grid is a sequence of rows.
+    impl<T> Serialize for FenwickTree<T>
+    where
+        T: Serialize + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..self.len()).map(|i| self.get(i)))
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for FenwickTree<T>
+    where
+        T: Deserialize<'de> + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
+
+    impl<T> Serialize for RangeFenwickTree<T>
+    where
+        T: Serialize + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..self.len()).map(|i| self.get(i)))
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for RangeFenwickTree<T>
+    where
+        T: Deserialize<'de> + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
+
+    impl<T> Serialize for FenwickTree2D<T>
+    where
+        T: Serialize + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let rows = (0..self.rows).map(|r| (0..self.cols).map(|c| self.get(r, c)).collect());
+            serializer.collect_seq(rows.collect::<Vec<Vec<T>>>())
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for FenwickTree2D<T>
+    where
+        T: Deserialize<'de> + Copy + Default + AddAssign + Sub<Output = T>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let grid = Vec::<Vec<T>>::deserialize(deserializer)?;
+            let cols = grid.first().map_or(0, Vec::len);
+            if grid.iter().any(|row| row.len() != cols) {
+                return Err(de::Error::custom("rows must all have the same length"));
+            }
+
+            let mut tree = Self::new(grid.len(), cols);
+            for (r, row) in grid.into_iter().enumerate() {
+                for (c, value) in row.into_iter().enumerate() {
+                    tree.add(r, c, value);
+                }
+            }
+            Ok(tree)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    #[test]
+    fn test_point_update_and_prefix_sum() {
+        let mut tree: FenwickTree<i32> = [3, 1, 4, 1, 5, 9, 2, 6].into_iter().collect();
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.prefix_sum(0), 0);
+        assert_eq!(tree.prefix_sum(3), 8);
+        assert_eq!(tree.sum(..), 31);
+        assert_eq!(tree.sum(2..=4), 10);
+
+        tree.add(2, -4);
+        tree.set(7, 0);
+        assert_eq!(tree.get(2), 0);
+        assert_eq!(tree.get(7), 0);
+        assert_eq!(tree.sum(..), 21);
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let mut rng = Lcg(17);
+        let len = 333;
+        let mut reference = vec![0i64; len];
+        let mut tree = FenwickTree::new(len);
+
+        for _ in 0..3000 {
+            let i = rng.next() as usize % len;
+            let delta = rng.next() as i64 % 201 - 100;
+            tree.add(i, delta);
+            reference[i] += delta;
+
+            let (l, r) = rng.range(len);
+            assert_eq!(tree.sum(l..r), reference[l..r].iter().sum::<i64>());
+        }
+
+        let rebuilt: FenwickTree<i64> = reference.iter().copied().collect();
+        for end in 0..=len {
+            assert_eq!(rebuilt.prefix_sum(end), tree.prefix_sum(end));
+        }
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let tree: FenwickTree<u32> = [2, 0, 3, 1, 0, 4].into_iter().collect();
+        assert_eq!(tree.lower_bound(0), Some(0));
+        assert_eq!(tree.lower_bound(1), Some(1));
+        assert_eq!(tree.lower_bound(2), Some(1));
+        assert_eq!(tree.lower_bound(3), Some(3));
+        assert_eq!(tree.lower_bound(6), Some(4));
+        assert_eq!(tree.lower_bound(7), Some(6));
+        assert_eq!(tree.lower_bound(10), Some(6));
+        assert_eq!(tree.lower_bound(11), None);
+        assert_eq!(FenwickTree::<u32>::new(0).lower_bound(1), None);
+
+        let mut rng = Lcg(71);
+        let values: Vec<u64> = (0..500).map(|_| rng.next() % 4).collect();
+        let tree: FenwickTree<u64> = values.iter().copied().collect();
+        for target in 0..=values.iter().sum::<u64>() + 1 {
+            let expected =
+                (0..=values.len()).find(|&end| values[..end].iter().sum::<u64>() >= target);
+            assert_eq!(tree.lower_bound(target), expected);
+        }
+    }
+
+    #[test]
+    fn test_range_update_against_brute_force() {
+        let mut rng = Lcg(7);
+        let len = 200;
+        let mut reference: Vec<i64> = (0..len).map(|_| rng.next() as i64 % 50).collect();
+        let mut tree: RangeFenwickTree<i64> = reference.iter().copied().collect();
+        assert_eq!(tree.len(), len);
+
+        for _ in 0..2000 {
+            let (l, r) = rng.range(len);
+            let delta = rng.next() as i64 % 41 - 20;
+            tree.add(l..r, delta);
+            reference[l..r].iter_mut().for_each(|v| *v += delta);
+
+            let (l, r) = rng.range(len);
+            assert_eq!(tree.sum(l..r), reference[l..r].iter().sum::<i64>());
+            let i = rng.next() as usize % len;
+            assert_eq!(tree.get(i), reference[i]);
+        }
+    }
+
+    #[test]
+    fn test_range_update_with_floats() {
+        let mut tree = RangeFenwickTree::new(4);
+        tree.add(1..3, 0.5);
+        tree.add(.., 1.0);
+        assert_eq!(tree.sum(..), 5.0);
+        assert_eq!(tree.get(2), 1.5);
+        assert_eq!(tree.prefix_sum(1), 1.0);
+    }
+
+    #[test]
+    fn test_2d_against_brute_force() {
+        let mut rng = Lcg(2);
+        let (rows, cols) = (23, 17);
+        let mut reference = vec![vec![0i64; cols]; rows];
+        let mut tree = FenwickTree2D::new(rows, cols);
+        assert_eq!((tree.rows(), tree.cols()), (rows, cols));
+
+        for _ in 0..2000 {
+            let (i, j) = (rng.next() as usize % rows, rng.next() as usize % cols);
+            let delta = rng.next() as i64 % 21 - 10;
+            tree.add(i, j, delta);
+            reference[i][j] += delta;
+
+            let (top, bottom) = rng.range(rows);
+            let (left, right) = rng.range(cols);
+            let expected: i64 = reference[top..bottom]
+                .iter()
+                .map(|row| row[left..right].iter().sum::<i64>())
+                .sum();
+            assert_eq!(tree.sum(top..bottom, left..right), expected);
+        }
+        assert_eq!(tree.get(3, 4), reference[3][4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_add_out_of_bounds() {
+        let mut tree = FenwickTree::new(3);
+        tree.add(3, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: FenwickTree<i64> = [3, -1, 4, 1, 5].into_iter().collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[3,-1,4,1,5]");
+        let back: FenwickTree<i64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.prefix_sum(5), 12);
+
+        let mut tree = RangeFenwickTree::new(4);
+        tree.add(1..3, 7i64);
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[0,7,7,0]");
+        let back: RangeFenwickTree<i64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.sum(..), 14);
+
+        let mut grid = FenwickTree2D::new(2, 3);
+        grid.add(0, 2, 4i64);
+        grid.add(1, 0, -2);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(json, "[[0,0,4],[-2,0,0]]");
+        let back: FenwickTree2D<i64> = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.rows(), back.cols()), (2, 3));
+        assert_eq!(back.sum(.., ..), 2);
+        assert!(serde_json::from_str::<FenwickTree2D<i64>>("[[1,2],[3]]").is_err());
+    }
+}
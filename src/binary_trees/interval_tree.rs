@@ -0,0 +1,675 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::FusedIterator,
+    ops::{Bound, Range},
+};
+
+use super::{
+    render::{self, DotOptions},
+    validate::{walk, InvariantError, Violation},
+};
+
+type Link<K, V> = Option<Box<IntervalNode<K, V>>>;
+
+#[derive(Debug)]
+pub(crate) struct IntervalNode<K, V> {
+    left: Link<K, V>,
+    right: Link<K, V>,
+    interval: Range<K>,
+    value: V,
+    /// Largest end point anywhere in this subtree.
+    max_end: K,
+    height: usize,
+}
+
+/// An AVL tree of half-open intervals ordered by start point. Every node also
+/// caches the largest end point in its subtree, so queries can skip any
+/// subtree whose intervals all end too early.
+///
+/// That pruning bounds a query returning `k` intervals by `O((k + 1) log n)`,
+/// not `O(log n + k)`: results can sit in `k` different subtrees, each behind
+/// a path of intervals that end too early.
+///
+/// Intervals only overlap when they share a point, so an empty interval such
+/// as `3..3` can be stored but is never returned by a query.
+#[derive(Debug)]
+pub struct IntervalTree<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+fn cmp_intervals<K: Ord>(a: &Range<K>, b: &Range<K>) -> Ordering {
+    a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+}
+
+impl<K, V> IntervalNode<K, V> {
+    fn parts(&self) -> (&K, Option<&Self>, Option<&Self>) {
+        (
+            &self.interval.start,
+            self.left.as_deref(),
+            self.right.as_deref(),
+        )
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn height(link: &Link<K, V>) -> usize {
+        link.as_ref().map_or(0, |n| n.height)
+    }
+
+    fn balance_factor(&self) -> isize {
+        Self::height(&self.left) as isize - Self::height(&self.right) as isize
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalNode<K, V> {
+    fn new(interval: Range<K>, value: V) -> Box<Self> {
+        Box::new(IntervalNode {
+            left: None,
+            right: None,
+            max_end: interval.end.clone(),
+            interval,
+            value,
+            height: 1,
+        })
+    }
+
+    fn update(&mut self) {
+        self.height = 1 + Self::height(&self.left).max(Self::height(&self.right));
+        let mut max_end = &self.interval.end;
+        for child in [&self.left, &self.right].into_iter().flatten() {
+            max_end = max_end.max(&child.max_end);
+        }
+        self.max_end = max_end.clone();
+    }
+
+    fn rotate_left(mut node: Box<Self>) -> Box<Self> {
+        let mut pivot = node.right.take().unwrap();
+        node.right = pivot.left.take();
+        node.update();
+        pivot.left = Some(node);
+        pivot.update();
+        pivot
+    }
+
+    fn rotate_right(mut node: Box<Self>) -> Box<Self> {
+        let mut pivot = node.left.take().unwrap();
+        node.left = pivot.right.take();
+        node.update();
+        pivot.right = Some(node);
+        pivot.update();
+        pivot
+    }
+
+    fn rebalance(mut node: Box<Self>) -> Box<Self> {
+        node.update();
+        let balance = node.balance_factor();
+        if balance > 1 {
+            if node.left.as_ref().unwrap().balance_factor() < 0 {
+                node.left = Some(Self::rotate_left(node.left.take().unwrap()));
+            }
+            Self::rotate_right(node)
+        } else if balance < -1 {
+            if node.right.as_ref().unwrap().balance_factor() > 0 {
+                node.right = Some(Self::rotate_right(node.right.take().unwrap()));
+            }
+            Self::rotate_left(node)
+        } else {
+            node
+        }
+    }
+
+    fn insert(link: Link<K, V>, new: Box<Self>) -> Box<Self> {
+        let mut node = match link {
+            Some(node) => node,
+            None => return new,
+        };
+
+        if cmp_intervals(&new.interval, &node.interval) == Ordering::Less {
+            node.left = Some(Self::insert(node.left.take(), new));
+        } else {
+            node.right = Some(Self::insert(node.right.take(), new));
+        }
+        Self::rebalance(node)
+    }
+
+    fn remove(link: Link<K, V>, interval: &Range<K>) -> (Link<K, V>, Option<Box<Self>>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None),
+        };
+
+        let removed = match cmp_intervals(interval, &node.interval) {
+            Ordering::Less => {
+                let (left, removed) = Self::remove(node.left.take(), interval);
+                node.left = left;
+                removed
+            }
+            Ordering::Greater => {
+                let (right, removed) = Self::remove(node.right.take(), interval);
+                node.right = right;
+                removed
+            }
+            Ordering::Equal => {
+                let (left, right) = (node.left.take(), node.right.take());
+                let replacement = match (left, right) {
+                    (None, child) | (child, None) => child,
+                    (Some(left), Some(right)) => {
+                        let (right, mut successor) = Self::take_min(right);
+                        successor.left = Some(left);
+                        successor.right = right;
+                        Some(Self::rebalance(successor))
+                    }
+                };
+                return (replacement, Some(node));
+            }
+        };
+        (Some(Self::rebalance(node)), removed)
+    }
+
+    fn take_min(mut node: Box<Self>) -> (Link<K, V>, Box<Self>) {
+        match node.left.take() {
+            Some(left) => {
+                let (left, min) = Self::take_min(left);
+                node.left = left;
+                (Some(Self::rebalance(node)), min)
+            }
+            None => (node.right.take(), node),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalTree<K, V> {
+    pub fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn height(&self) -> usize {
+        IntervalNode::height(&self.root)
+    }
+
+    /// Adds an interval. Equal intervals are kept side by side rather than
+    /// replacing each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval.start > interval.end`.
+    pub fn insert(&mut self, interval: Range<K>, value: V) {
+        assert!(
+            interval.start <= interval.end,
+            "interval start must not exceed its end"
+        );
+        let node = IntervalNode::new(interval, value);
+        self.root = Some(IntervalNode::insert(self.root.take(), node));
+        self.len += 1;
+    }
+
+    /// Removes one interval equal to `interval` and returns its value.
+    pub fn remove(&mut self, interval: &Range<K>) -> Option<V> {
+        let (root, removed) = IntervalNode::remove(self.root.take(), interval);
+        self.root = root;
+        let removed = removed?;
+        self.len -= 1;
+        Some(removed.value)
+    }
+
+    /// Iterates over every interval sharing at least one point with `range`,
+    /// ordered by start point, in `O((k + 1) log n)` for `k` results.
+    pub fn overlapping(&self, range: Range<K>) -> Overlapping<'_, K, V> {
+        if range.start >= range.end {
+            return Overlapping::empty();
+        }
+        Overlapping::new(&self.root, range.start, Bound::Excluded(range.end))
+    }
+
+    /// Iterates over every interval containing `point`, ordered by start
+    /// point, in `O((k + 1) log n)` for `k` results.
+    pub fn containing(&self, point: K) -> Overlapping<'_, K, V> {
+        Overlapping::new(&self.root, point.clone(), Bound::Included(point))
+    }
+
+    /// Checks that the intervals are ordered by start point, that the tree is
+    /// balanced and that every cached height and maximum end point is current.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            true,
+            IntervalNode::parts,
+            |n, left, right| {
+                let (left_height, left_end): (usize, Option<&K>) =
+                    left.map_or((0, None), |(h, e)| (h, Some(e)));
+                let (right_height, right_end) = right.map_or((0, None), |(h, e)| (h, Some(e)));
+                let height = 1 + left_height.max(right_height);
+                let max_end = [left_end, right_end]
+                    .into_iter()
+                    .flatten()
+                    .fold(&n.interval.end, |a, b| a.max(b));
+
+                if left_height.abs_diff(right_height) > 1 {
+                    return Err(Violation::Unbalanced {
+                        left: left_height,
+                        right: right_height,
+                    });
+                }
+                if n.height != height {
+                    return Err(Violation::StaleHeight {
+                        cached: n.height,
+                        actual: height,
+                    });
+                }
+                if n.max_end != *max_end {
+                    return Err(Violation::StaleMaxEnd);
+                }
+                Ok((height, max_end))
+            },
+        )
+        .map(|_| ())
+    }
+}
+
+impl<K, V> IntervalTree<K, V> {
+    /// Iterates over every interval, ordered by start point and then by end
+    /// point.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<K: fmt::Display, V> IntervalTree<K, V> {
+    /// Renders the intervals as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        render::to_dot(
+            self.root.as_deref(),
+            IntervalNode::children,
+            IntervalNode::label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> fmt::Display for IntervalTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        render::write_ascii(
+            f,
+            self.root.as_deref(),
+            IntervalNode::children,
+            IntervalNode::label,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> IntervalNode<K, V> {
+    fn label(&self) -> String {
+        format!("[{}, {})", self.interval.start, self.interval.end)
+    }
+}
+
+/// Intervals that start within an upper bound and end after a lower one.
+pub struct Overlapping<'a, K, V> {
+    stack: Vec<&'a IntervalNode<K, V>>,
+    lo: Option<K>,
+    hi: Bound<K>,
+}
+
+impl<'a, K: Ord, V> Overlapping<'a, K, V> {
+    fn new(root: &'a Link<K, V>, lo: K, hi: Bound<K>) -> Self {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            lo: Some(lo),
+            hi,
+        };
+        iter.push_left(root);
+        iter
+    }
+
+    fn empty() -> Self {
+        Overlapping {
+            stack: Vec::new(),
+            lo: None,
+            hi: Bound::Unbounded,
+        }
+    }
+
+    fn ends_after_lo(&self, end: &K) -> bool {
+        self.lo.as_ref().is_some_and(|lo| end > lo)
+    }
+
+    fn starts_before_hi(&self, start: &K) -> bool {
+        match &self.hi {
+            Bound::Included(hi) => start <= hi,
+            Bound::Excluded(hi) => start < hi,
+            Bound::Unbounded => true,
+        }
+    }
+
+    // A subtree whose intervals all end by `lo` can hold no results, so the
+    // descent stops there along with everything to its right.
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            if !self.ends_after_lo(&node.max_end) {
+                break;
+            }
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Overlapping<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            // Everything still to come starts at or after this node.
+            if !self.starts_before_hi(&node.interval.start) {
+                self.stack.clear();
+                return None;
+            }
+
+            self.push_left(&node.right);
+            let interval = &node.interval;
+            if interval.start < interval.end && self.ends_after_lo(&interval.end) {
+                return Some((interval, &node.value));
+            }
+        }
+        None
+    }
+}
+
+impl<K: Ord, V> FusedIterator for Overlapping<'_, K, V> {}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a IntervalNode<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some((&node.interval, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a IntervalTree<K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Clone, V> FromIterator<(Range<K>, V)> for IntervalTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord + Clone, V> Extend<(Range<K>, V)> for IntervalTree<K, V> {
+    fn extend<I: IntoIterator<Item = (Range<K>, V)>>(&mut self, iter: I) {
+        for (interval, value) in iter {
+            self.insert(interval, value);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::ops::Range;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::IntervalTree;
+
+    // Serialized as a sequence of `(interval, value)` pairs ordered by start.
+    impl<K: Serialize, V: Serialize> Serialize for IntervalTree<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for IntervalTree<K, V>
+    where
+        K: Deserialize<'de> + Ord + Clone,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(Range<K>, V)>::deserialize(deserializer)?;
+            if entries
+                .iter()
+                .any(|(interval, _)| interval.start > interval.end)
+            {
+                return Err(de::Error::custom("interval start must not exceed its end"));
+            }
+            Ok(entries.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    fn collect<'a>(
+        iter: impl Iterator<Item = (&'a Range<i64>, &'a usize)>,
+    ) -> Vec<(i64, i64, usize)> {
+        let mut found: Vec<_> = iter.map(|(r, &v)| (r.start, r.end, v)).collect();
+        found.sort();
+        found
+    }
+
+    fn brute_force(
+        reference: &[(Range<i64>, usize)],
+        keep: impl Fn(&Range<i64>) -> bool,
+    ) -> Vec<(i64, i64, usize)> {
+        let mut found: Vec<_> = reference
+            .iter()
+            .filter(|(r, _)| keep(r))
+            .map(|(r, v)| (r.start, r.end, *v))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_overlap_and_stabbing() {
+        let tree: IntervalTree<i32, &str> = [
+            (9..12, "standup"),
+            (13..17, "review"),
+            (10..11, "call"),
+            (15..16, "sync"),
+            (17..18, "retro"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.validate(), Ok(()));
+
+        let names =
+            |iter: Overlapping<'_, i32, &'static str>| iter.map(|(_, v)| *v).collect::<Vec<_>>();
+        assert_eq!(names(tree.overlapping(11..15)), ["standup", "review"]);
+        assert_eq!(names(tree.overlapping(12..13)), Vec::<&str>::new());
+        assert_eq!(names(tree.overlapping(0..100)).len(), 5);
+        assert_eq!(names(tree.containing(10)), ["standup", "call"]);
+        // End points are exclusive.
+        assert_eq!(names(tree.containing(17)), ["retro"]);
+        assert_eq!(names(tree.containing(12)), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_empty_intervals_never_match() {
+        let mut tree = IntervalTree::new();
+        tree.insert(5..5, 0);
+        tree.insert(0..10, 1);
+        assert_eq!(tree.overlapping(0..10).count(), 1);
+        assert_eq!(tree.containing(5).count(), 1);
+        assert_eq!(tree.overlapping(5..5).count(), 0);
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "interval start must not exceed its end")]
+    fn test_insert_rejects_reversed_interval() {
+        let mut tree = IntervalTree::new();
+        let (start, end) = (3, 1);
+        tree.insert(start..end, ());
+    }
+
+    #[test]
+    fn test_duplicates_and_remove() {
+        let mut tree = IntervalTree::new();
+        tree.insert(1..4, 'a');
+        tree.insert(1..4, 'b');
+        tree.insert(2..3, 'c');
+        assert_eq!(tree.containing(1).count(), 2);
+
+        let first = tree.remove(&(1..4)).unwrap();
+        let second = tree.remove(&(1..4)).unwrap();
+        assert_eq!([first.min(second), first.max(second)], ['a', 'b']);
+        assert_eq!(tree.remove(&(1..4)), None);
+        assert_eq!(tree.remove(&(2..4)), None);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_random_against_brute_force() {
+        let mut rng = Lcg(15);
+        let mut tree = IntervalTree::new();
+        let mut reference: Vec<(Range<i64>, usize)> = Vec::new();
+
+        for step in 0..4000 {
+            if rng.next().is_multiple_of(3) && !reference.is_empty() {
+                let (interval, _) = reference[rng.next() as usize % reference.len()].clone();
+                let value = tree.remove(&interval).unwrap();
+                let at = reference
+                    .iter()
+                    .position(|(r, v)| *r == interval && *v == value)
+                    .unwrap();
+                reference.swap_remove(at);
+            } else {
+                let start = (rng.next() % 1000) as i64;
+                let end = start + (rng.next() % 60) as i64;
+                tree.insert(start..end, step);
+                reference.push((start..end, step));
+            }
+            assert_eq!(tree.len(), reference.len());
+
+            let lo = (rng.next() % 1100) as i64 - 50;
+            let hi = lo + (rng.next() % 80) as i64;
+            assert_eq!(
+                collect(tree.overlapping(lo..hi)),
+                brute_force(&reference, |r| r.start < r.end
+                    && r.start < hi
+                    && lo < r.end
+                    && lo < hi)
+            );
+            assert_eq!(
+                collect(tree.containing(lo)),
+                brute_force(&reference, |r| r.start <= lo && lo < r.end)
+            );
+
+            if step % 200 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+            }
+        }
+
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.height() <= 2 * (usize::BITS - tree.len().leading_zeros()) as usize);
+        let starts: Vec<_> = tree.iter().map(|(r, _)| (r.start, r.end)).collect();
+        assert!(starts.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_results_are_sorted_by_start() {
+        let tree: IntervalTree<i32, ()> = (0..100).rev().map(|i| (i..i + 10, ())).collect();
+        let starts: Vec<_> = tree.overlapping(40..45).map(|(r, _)| r.start).collect();
+        assert_eq!(starts, (31..45).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_validate_detects_stale_max_end() {
+        let mut tree: IntervalTree<i32, ()> = (0..7).map(|i| (i..i + 1, ())).collect();
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().max_end = 100;
+        let err = tree.validate().unwrap_err();
+        assert_eq!(err.path, "L");
+        assert_eq!(err.violation, Violation::StaleMaxEnd);
+    }
+
+    #[test]
+    fn test_render() {
+        let tree: IntervalTree<i32, ()> = [(2..5, ()), (1..3, ())].into_iter().collect();
+        assert_eq!(tree.to_string(), "[2, 5)\n|-- [1, 3)\n`-- nil\n");
+        assert!(tree.to_dot().contains("n1 [label=\"[1, 3)\"];"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: IntervalTree<i32, &str> = [(5..10, "b"), (1..4, "a"), (7..7, "empty")]
+            .into_iter()
+            .collect();
+        let json = serde_json::to_string(&tree).unwrap();
+
+        let back: IntervalTree<i32, String> = serde_json::from_str(&json).unwrap();
+        assert!(back
+            .iter()
+            .map(|(range, v)| (range.clone(), v.as_str()))
+            .eq(tree.iter().map(|(range, &v)| (range.clone(), v))));
+        assert_eq!(back.validate(), Ok(()));
+
+        let reversed = r#"[[{"start":3,"end":1},"x"]]"#;
+        assert!(serde_json::from_str::<IntervalTree<i32, String>>(reversed).is_err());
+    }
+}
@@ -1,2 +1,31 @@
-mod avl;
-mod bst;
+//! Binary search trees.
+
+pub mod avl;
+pub mod bst;
+pub mod bst_map;
+pub mod fenwick_tree;
+pub mod interval_tree;
+pub mod persistent_map;
+pub mod rb_tree;
+pub mod render;
+pub mod scapegoat_tree;
+pub mod segment_tree;
+pub mod splay_tree;
+pub mod treap;
+pub mod validate;
+pub mod weight_balanced;
+
+pub use avl::Avl;
+pub use bst::Bst;
+pub use bst_map::BstMap;
+pub use fenwick_tree::{FenwickTree, FenwickTree2D, RangeFenwickTree};
+pub use interval_tree::IntervalTree;
+pub use persistent_map::PersistentMap;
+pub use rb_tree::RbTree;
+pub use render::DotOptions;
+pub use scapegoat_tree::ScapegoatTree;
+pub use segment_tree::{Action, Monoid, SegmentTree};
+pub use splay_tree::SplayTree;
+pub use treap::{ImplicitTreap, Treap};
+pub use validate::{InvariantError, Violation};
+pub use weight_balanced::WeightBalancedTree;
@@ -0,0 +1,502 @@
+use std::{cmp::Ordering, fmt, iter::FusedIterator, rc::Rc};
+
+use super::{
+    render::{self, DotOptions},
+    validate::{walk, InvariantError, Violation},
+};
+
+type Link<K, V> = Option<Rc<PersistentNode<K, V>>>;
+
+// Nodes are never modified once built. The entry sits behind its own `Rc` so
+// that copying a node on an updated path never copies a key or value.
+pub(crate) struct PersistentNode<K, V> {
+    left: Link<K, V>,
+    right: Link<K, V>,
+    entry: Rc<(K, V)>,
+    height: usize,
+}
+
+/// An immutable ordered map. `insert` and `remove` leave `self` untouched and
+/// return a new version that shares every node off the updated path, so each
+/// version costs `O(log n)` new nodes and cloning a map is `O(1)`.
+///
+/// Nodes are reference counted with `Rc`, so versions cannot be sent across
+/// threads.
+pub struct PersistentMap<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+impl<K, V> Clone for PersistentMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V> PersistentNode<K, V> {
+    fn parts(&self) -> (&K, Option<&Self>, Option<&Self>) {
+        (&self.entry.0, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn height(link: &Link<K, V>) -> usize {
+        link.as_ref().map_or(0, |n| n.height)
+    }
+
+    fn new(left: Link<K, V>, entry: Rc<(K, V)>, right: Link<K, V>) -> Rc<Self> {
+        let height = 1 + Self::height(&left).max(Self::height(&right));
+        Rc::new(PersistentNode {
+            left,
+            right,
+            entry,
+            height,
+        })
+    }
+
+    /// Builds a node from subtrees whose heights differ by at most two,
+    /// rotating fresh copies into place if they differ by two.
+    fn balance(left: Link<K, V>, entry: Rc<(K, V)>, right: Link<K, V>) -> Rc<Self> {
+        let (lh, rh) = (Self::height(&left), Self::height(&right));
+        if lh > rh + 1 {
+            let l = left.unwrap();
+            if Self::height(&l.left) >= Self::height(&l.right) {
+                let right = Self::new(l.right.clone(), entry, right);
+                Self::new(l.left.clone(), l.entry.clone(), Some(right))
+            } else {
+                let lr = l.right.as_ref().unwrap();
+                let left = Self::new(l.left.clone(), l.entry.clone(), lr.left.clone());
+                let right = Self::new(lr.right.clone(), entry, right);
+                Self::new(Some(left), lr.entry.clone(), Some(right))
+            }
+        } else if rh > lh + 1 {
+            let r = right.unwrap();
+            if Self::height(&r.right) >= Self::height(&r.left) {
+                let left = Self::new(left, entry, r.left.clone());
+                Self::new(Some(left), r.entry.clone(), r.right.clone())
+            } else {
+                let rl = r.left.as_ref().unwrap();
+                let left = Self::new(left, entry, rl.left.clone());
+                let right = Self::new(rl.right.clone(), r.entry.clone(), r.right.clone());
+                Self::new(Some(left), rl.entry.clone(), Some(right))
+            }
+        } else {
+            Self::new(left, entry, right)
+        }
+    }
+
+    fn remove_min(node: &Rc<Self>) -> (Link<K, V>, Rc<(K, V)>) {
+        match &node.left {
+            Some(left) => {
+                let (left, min) = Self::remove_min(left);
+                let node = Self::balance(left, node.entry.clone(), node.right.clone());
+                (Some(node), min)
+            }
+            None => (node.right.clone(), node.entry.clone()),
+        }
+    }
+}
+
+impl<K: Ord, V> PersistentNode<K, V> {
+    /// Returns the updated copy of `link` and whether `key` replaced an
+    /// existing entry.
+    fn insert(link: &Link<K, V>, entry: Rc<(K, V)>) -> (Rc<Self>, bool) {
+        let node = match link {
+            Some(node) => node,
+            None => return (Self::new(None, entry, None), false),
+        };
+
+        match entry.0.cmp(&node.entry.0) {
+            Ordering::Less => {
+                let (left, replaced) = Self::insert(&node.left, entry);
+                let node = Self::balance(Some(left), node.entry.clone(), node.right.clone());
+                (node, replaced)
+            }
+            Ordering::Greater => {
+                let (right, replaced) = Self::insert(&node.right, entry);
+                let node = Self::balance(node.left.clone(), node.entry.clone(), Some(right));
+                (node, replaced)
+            }
+            Ordering::Equal => {
+                let node = Self::new(node.left.clone(), entry, node.right.clone());
+                (node, true)
+            }
+        }
+    }
+
+    /// Returns the copy of `link` without `key`, or `None` if `key` is absent
+    /// and `link` can be shared as it is.
+    fn remove(link: &Link<K, V>, key: &K) -> Option<Link<K, V>> {
+        let node = link.as_ref()?;
+        let updated = match key.cmp(&node.entry.0) {
+            Ordering::Less => {
+                let left = Self::remove(&node.left, key)?;
+                Self::balance(left, node.entry.clone(), node.right.clone())
+            }
+            Ordering::Greater => {
+                let right = Self::remove(&node.right, key)?;
+                Self::balance(node.left.clone(), node.entry.clone(), right)
+            }
+            Ordering::Equal => match (&node.left, &node.right) {
+                (None, child) | (child, None) => return Some(child.clone()),
+                (Some(left), Some(right)) => {
+                    let (right, successor) = Self::remove_min(right);
+                    Self::balance(Some(left.clone()), successor, right)
+                }
+            },
+        };
+        Some(Some(updated))
+    }
+}
+
+impl<K, V> Default for PersistentMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> PersistentMap<K, V> {
+    pub fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn height(&self) -> usize {
+        PersistentNode::height(&self.root)
+    }
+
+    /// Whether both maps are the same version, or versions that share their
+    /// whole tree.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some((&node.entry.0, &node.entry.1))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some((&node.entry.0, &node.entry.1))
+    }
+}
+
+impl<K: Ord, V> PersistentMap<K, V> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            match key.cmp(&node.entry.0) {
+                Ordering::Less => link = &node.left,
+                Ordering::Greater => link = &node.right,
+                Ordering::Equal => return Some(&node.entry.1),
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a new version with `key` mapped to `value`, replacing any
+    /// previous value.
+    #[must_use]
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let (root, replaced) = PersistentNode::insert(&self.root, Rc::new((key, value)));
+        Self {
+            root: Some(root),
+            len: if replaced { self.len } else { self.len + 1 },
+        }
+    }
+
+    /// Returns a new version without `key`. If `key` is absent the new
+    /// version shares the whole tree with `self`.
+    #[must_use]
+    pub fn remove(&self, key: &K) -> Self {
+        match PersistentNode::remove(&self.root, key) {
+            Some(root) => Self {
+                root,
+                len: self.len - 1,
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Checks ordering, the AVL balance condition and the cached heights,
+    /// reporting the first node that breaks any of them.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            false,
+            PersistentNode::parts,
+            |n, left, right| {
+                let (left, right): (usize, usize) = (left.unwrap_or(0), right.unwrap_or(0));
+                let height = 1 + left.max(right);
+                if left.abs_diff(right) > 1 {
+                    return Err(Violation::Unbalanced { left, right });
+                }
+                if n.height != height {
+                    return Err(Violation::StaleHeight {
+                        cached: n.height,
+                        actual: height,
+                    });
+                }
+                Ok(height)
+            },
+        )
+        .map(|_| ())
+    }
+}
+
+impl<K: fmt::Display, V> PersistentMap<K, V> {
+    /// Renders the keys as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &PersistentNode<K, V>| n.entry.0.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            PersistentNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> fmt::Display for PersistentMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &PersistentNode<K, V>| n.entry.0.to_string();
+        render::write_ascii(f, self.root.as_deref(), PersistentNode::children, label)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for PersistentMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a PersistentNode<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some((&node.entry.0, &node.entry.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a PersistentMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for PersistentMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |map, (key, value)| map.insert(key, value))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::PersistentMap;
+
+    // Serialized as a sequence of `(key, value)` pairs in key order.
+    impl<K: Serialize, V: Serialize> Serialize for PersistentMap<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for PersistentMap<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<(K, V)>::deserialize(deserializer)?
+                .into_iter()
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_old_versions_stay_valid() {
+        let empty = PersistentMap::new();
+        let one = empty.insert(1, "one");
+        let two = one.insert(2, "two");
+        let replaced = two.insert(1, "uno");
+        let removed = replaced.remove(&2);
+
+        assert!(empty.is_empty());
+        assert_eq!(one.iter().collect::<Vec<_>>(), [(&1, &"one")]);
+        assert_eq!(two.len(), 2);
+        assert_eq!(two.get(&1), Some(&"one"));
+        assert_eq!(replaced.len(), 2);
+        assert_eq!(replaced.get(&1), Some(&"uno"));
+        assert_eq!(removed.len(), 1);
+        assert!(!removed.contains_key(&2));
+        assert!(two.contains_key(&2));
+    }
+
+    #[test]
+    fn test_updates_share_untouched_nodes() {
+        let base: PersistentMap<i32, i32> = (0..1023).map(|k| (k, k)).collect();
+        let updated = base.insert(1000, -1);
+
+        // Only the path to the updated key is new, so the left half of the
+        // tree is still the very same allocation.
+        let (a, b) = (base.root.as_ref().unwrap(), updated.root.as_ref().unwrap());
+        assert!(!Rc::ptr_eq(a, b));
+        assert!(Rc::ptr_eq(
+            a.left.as_ref().unwrap(),
+            b.left.as_ref().unwrap()
+        ));
+        assert_eq!(Rc::strong_count(a.left.as_ref().unwrap()), 2);
+        assert_eq!(base.get(&1000), Some(&1000));
+        assert_eq!(updated.get(&1000), Some(&-1));
+
+        assert!(base.remove(&5000).ptr_eq(&base));
+        assert!(base.clone().ptr_eq(&base));
+        assert!(!updated.ptr_eq(&base));
+    }
+
+    #[test]
+    fn test_random_versions_against_btreemap() {
+        let mut rng = Lcg(18);
+        let mut versions = vec![(PersistentMap::new(), BTreeMap::new())];
+
+        for step in 0..3000 {
+            // Branch from a random earlier version, not just the latest one.
+            let from = if rng.next().is_multiple_of(4) {
+                rng.next() as usize % versions.len()
+            } else {
+                versions.len() - 1
+            };
+            let (map, reference) = &versions[from];
+            let key = rng.next() % 500;
+            let (map, mut reference) = (map.clone(), reference.clone());
+            let map = if rng.next().is_multiple_of(3) {
+                reference.remove(&key);
+                map.remove(&key)
+            } else {
+                reference.insert(key, step);
+                map.insert(key, step)
+            };
+            versions.push((map, reference));
+        }
+
+        for (map, reference) in &versions {
+            assert_eq!(map.len(), reference.len());
+            assert!(map.iter().eq(reference.iter()));
+            assert_eq!(map.first(), reference.first_key_value());
+            assert_eq!(map.last(), reference.last_key_value());
+        }
+        let (latest, _) = versions.last().unwrap();
+        assert_eq!(latest.validate(), Ok(()));
+        assert!(latest.height() <= 2 * (usize::BITS - latest.len().leading_zeros()) as usize);
+    }
+
+    #[test]
+    fn test_sorted_inserts_stay_balanced() {
+        let map: PersistentMap<u32, ()> = (0..10_000).map(|k| (k, ())).collect();
+        assert_eq!(map.validate(), Ok(()));
+        assert_eq!(map.height(), 14);
+
+        let map = (0..5000).fold(map, |map, k| map.remove(&(2 * k)));
+        assert_eq!(map.len(), 5000);
+        assert_eq!(map.validate(), Ok(()));
+        assert!(map.iter().map(|(k, _)| *k).eq((0..5000).map(|k| 2 * k + 1)));
+    }
+
+    #[test]
+    fn test_render_and_debug() {
+        let map: PersistentMap<i32, char> = [(2, 'b'), (1, 'a'), (3, 'c')].into_iter().collect();
+        assert_eq!(map.to_string(), "2\n|-- 1\n`-- 3\n");
+        assert!(map.to_dot().contains("n0 [label=\"2\"];"));
+        assert_eq!(format!("{map:?}"), "{1: 'a', 2: 'b', 3: 'c'}");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let map: PersistentMap<i32, i32> = (0..20).map(|i| (i, i * i)).collect();
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.starts_with("[[0,0],[1,1],[2,4]"));
+
+        let back: PersistentMap<i32, i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(map.iter()));
+        assert_eq!(back.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,1284 @@
+use std::{
+    borrow::Borrow, cmp::Ordering, collections::VecDeque, fmt, iter::FusedIterator, mem,
+    ops::RangeBounds, ptr,
+};
+
+use super::{
+    bst::{above_start, below_end, count_in_range},
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError, Violation},
+};
+
+type Link<K, V> = Option<Box<RBNode<K, V>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Color {
+    Red,
+    Black,
+}
+
+impl Color {
+    fn name(self) -> &'static str {
+        match self {
+            Color::Red => "red",
+            Color::Black => "black",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RBNode<K, V> {
+    left: Link<K, V>,
+    right: Link<K, V>,
+    key: K,
+    value: V,
+    color: Color,
+    size: usize,
+}
+
+/// A left-leaning red-black tree: every red link leans left, so each node
+/// corresponds to a 2-node or 3-node of a 2-3 tree and the height stays
+/// within `2 * log2(n + 1)`.
+///
+/// Its methods carry `Bst`'s names, so an `RbTree<T, ()>` can stand in for a
+/// `Bst<T>`; `insert` takes the value alongside the key and iterators yield
+/// `(key, value)` pairs.
+#[derive(Debug)]
+pub struct RbTree<K, V> {
+    root: Link<K, V>,
+}
+
+impl<K, V> RBNode<K, V> {
+    fn new(key: K, value: V) -> Box<Self> {
+        Box::new(RBNode {
+            left: None,
+            right: None,
+            key,
+            value,
+            color: Color::Red,
+            size: 1,
+        })
+    }
+
+    fn parts(&self) -> (&K, Option<&Self>, Option<&Self>) {
+        (&self.key, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn into_entry(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
+    fn is_red(link: &Link<K, V>) -> bool {
+        link.as_ref().is_some_and(|n| n.color == Color::Red)
+    }
+
+    fn is_left_red(link: &Link<K, V>) -> bool {
+        link.as_ref().is_some_and(|n| Self::is_red(&n.left))
+    }
+
+    fn size(link: &Link<K, V>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    fn rotate_left(mut node: Box<Self>) -> Box<Self> {
+        let mut pivot = node.right.take().unwrap();
+        node.right = pivot.left.take();
+        pivot.color = node.color;
+        node.color = Color::Red;
+        node.update_size();
+        pivot.left = Some(node);
+        pivot.update_size();
+        pivot
+    }
+
+    fn rotate_right(mut node: Box<Self>) -> Box<Self> {
+        let mut pivot = node.left.take().unwrap();
+        node.left = pivot.right.take();
+        pivot.color = node.color;
+        node.color = Color::Red;
+        node.update_size();
+        pivot.right = Some(node);
+        pivot.update_size();
+        pivot
+    }
+
+    fn flip_colors(&mut self) {
+        fn flip(color: &mut Color) {
+            *color = match color {
+                Color::Red => Color::Black,
+                Color::Black => Color::Red,
+            };
+        }
+
+        flip(&mut self.color);
+        if let Some(ref mut left) = self.left {
+            flip(&mut left.color);
+        }
+        if let Some(ref mut right) = self.right {
+            flip(&mut right.color);
+        }
+    }
+
+    // Restores the left-leaning invariants on the way back up from an
+    // insert or delete.
+    fn balance(mut node: Box<Self>) -> Box<Self> {
+        if Self::is_red(&node.right) && !Self::is_red(&node.left) {
+            node = Self::rotate_left(node);
+        }
+        if Self::is_red(&node.left) && Self::is_left_red(&node.left) {
+            node = Self::rotate_right(node);
+        }
+        if Self::is_red(&node.left) && Self::is_red(&node.right) {
+            node.flip_colors();
+        }
+
+        node.update_size();
+        node
+    }
+
+    fn move_red_left(mut node: Box<Self>) -> Box<Self> {
+        node.flip_colors();
+        if Self::is_left_red(&node.right) {
+            let right = node.right.take().unwrap();
+            node.right = Some(Self::rotate_right(right));
+            node = Self::rotate_left(node);
+            node.flip_colors();
+        }
+        node
+    }
+
+    fn move_red_right(mut node: Box<Self>) -> Box<Self> {
+        node.flip_colors();
+        if Self::is_left_red(&node.left) {
+            node = Self::rotate_right(node);
+            node.flip_colors();
+        }
+        node
+    }
+
+    fn remove_min(mut node: Box<Self>) -> (Link<K, V>, Box<Self>) {
+        if node.left.is_none() {
+            return (None, node);
+        }
+
+        if !Self::is_red(&node.left) && !Self::is_left_red(&node.left) {
+            node = Self::move_red_left(node);
+        }
+
+        let left = node.left.take().unwrap();
+        let (new_left, min) = Self::remove_min(left);
+        node.left = new_left;
+        (Some(Self::balance(node)), min)
+    }
+
+    fn remove_max(mut node: Box<Self>) -> (Link<K, V>, Box<Self>) {
+        if Self::is_red(&node.left) {
+            node = Self::rotate_right(node);
+        }
+        if node.right.is_none() {
+            let left = node.left.take();
+            return (left, node);
+        }
+
+        if !Self::is_red(&node.right) && !Self::is_left_red(&node.right) {
+            node = Self::move_red_right(node);
+        }
+
+        let right = node.right.take().unwrap();
+        let (new_right, max) = Self::remove_max(right);
+        node.right = new_right;
+        (Some(Self::balance(node)), max)
+    }
+}
+
+impl<K: Ord, V> RBNode<K, V> {
+    fn insert(link: Link<K, V>, key: K, value: V) -> (Box<Self>, Option<V>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (Self::new(key, value), None),
+        };
+
+        let old = match key.cmp(&node.key) {
+            Ordering::Less => {
+                let (new_left, old) = Self::insert(node.left.take(), key, value);
+                node.left = Some(new_left);
+                old
+            }
+            Ordering::Greater => {
+                let (new_right, old) = Self::insert(node.right.take(), key, value);
+                node.right = Some(new_right);
+                old
+            }
+            Ordering::Equal => Some(mem::replace(&mut node.value, value)),
+        };
+
+        (Self::balance(node), old)
+    }
+
+    // `key` must be present in the subtree rooted at `node`.
+    fn remove<Q>(mut node: Box<Self>, key: &Q) -> (Link<K, V>, Box<Self>)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if key < node.key.borrow() {
+            if !Self::is_red(&node.left) && !Self::is_left_red(&node.left) {
+                node = Self::move_red_left(node);
+            }
+
+            let left = node.left.take().unwrap();
+            let (new_left, removed) = Self::remove(left, key);
+            node.left = new_left;
+            return (Some(Self::balance(node)), removed);
+        }
+
+        if Self::is_red(&node.left) {
+            node = Self::rotate_right(node);
+        }
+        if key == node.key.borrow() && node.right.is_none() {
+            return (None, node);
+        }
+
+        if !Self::is_red(&node.right) && !Self::is_left_red(&node.right) {
+            node = Self::move_red_right(node);
+        }
+
+        let right = node.right.take().unwrap();
+        let (new_right, removed) = if key == node.key.borrow() {
+            // Move the successor's entry into this node and hand back the
+            // successor node carrying the removed entry, so nothing is cloned.
+            let (new_right, mut successor) = Self::remove_min(right);
+            mem::swap(&mut node.key, &mut successor.key);
+            mem::swap(&mut node.value, &mut successor.value);
+            (new_right, successor)
+        } else {
+            Self::remove(right, key)
+        };
+        node.right = new_right;
+        (Some(Self::balance(node)), removed)
+    }
+}
+
+impl<K: Ord, V> Default for RbTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> RbTree<K, V> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (mut root, old) = RBNode::insert(self.root.take(), key, value);
+        root.color = Color::Black;
+        self.root = Some(root);
+        old
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).map(|node| &node.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => &mut node.left,
+                Ordering::Greater => &mut node.right,
+                Ordering::Equal => return Some(&mut node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.contains_key(key) {
+            return None;
+        }
+
+        let mut root = self.root.take().unwrap();
+        Self::redden_root(&mut root);
+        let (root, removed) = RBNode::remove(root, key);
+        self.set_root(root);
+        Some(removed.into_entry())
+    }
+
+    /// [`contains_key`](Self::contains_key) under the name `Bst` uses.
+    pub fn search(&self, key: K) -> bool {
+        self.contains_key(&key)
+    }
+
+    /// [`remove`](Self::remove) under the name `Bst` uses, dropping the entry.
+    pub fn delete(&mut self, key: K) {
+        self.remove(&key);
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        let mut root = self.root.take()?;
+        Self::redden_root(&mut root);
+        let (root, min) = RBNode::remove_min(root);
+        self.set_root(root);
+        Some(min.into_entry())
+    }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        let mut root = self.root.take()?;
+        Self::redden_root(&mut root);
+        let (root, max) = RBNode::remove_max(root);
+        self.set_root(root);
+        Some(max.into_entry())
+    }
+
+    /// Largest entry whose key is less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        self.closest(|k| k <= key, true)
+    }
+
+    /// Smallest entry whose key is greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        self.closest(|k| k >= key, false)
+    }
+
+    /// Largest entry whose key is strictly less than `key`.
+    pub fn predecessor(&self, key: &K) -> Option<(&K, &V)> {
+        self.closest(|k| k < key, true)
+    }
+
+    /// Smallest entry whose key is strictly greater than `key`.
+    pub fn successor(&self, key: &K) -> Option<(&K, &V)> {
+        self.closest(|k| k > key, false)
+    }
+
+    /// Number of keys strictly less than `key`.
+    pub fn rank(&self, key: &K) -> usize {
+        self.count_less(key, false)
+    }
+
+    pub fn count_range<R: RangeBounds<K>>(&self, range: R) -> usize {
+        count_in_range(&range, self.len(), |key, inclusive| {
+            self.count_less(key, inclusive)
+        })
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        let mut front = Vec::new();
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            if above_start(&node.key, range.start_bound()) {
+                front.push(node);
+                link = node.left.as_deref();
+            } else {
+                link = node.right.as_deref();
+            }
+        }
+
+        let mut back = Vec::new();
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            if below_end(&node.key, range.end_bound()) {
+                back.push(node);
+                link = node.right.as_deref();
+            } else {
+                link = node.left.as_deref();
+            }
+        }
+
+        let mut range = Range { front, back };
+        let is_empty = match (range.front.last(), range.back.last()) {
+            (Some(first), Some(last)) => first.key > last.key,
+            _ => true,
+        };
+        if is_empty {
+            range.front.clear();
+            range.back.clear();
+        }
+        range
+    }
+
+    /// Checks ordering, the red-black colour rules and the cached subtree
+    /// sizes.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.black_height().map(|_| ())
+    }
+
+    // Black height of the tree, or the first invariant violation found.
+    fn black_height(&self) -> Result<usize, InvariantError> {
+        if RBNode::is_red(&self.root) {
+            return Err(InvariantError {
+                path: String::new(),
+                violation: Violation::RedRoot,
+            });
+        }
+
+        let summary = walk(
+            self.root.as_deref(),
+            false,
+            RBNode::parts,
+            |node, left, right| {
+                if node.color == Color::Red
+                    && (RBNode::is_red(&node.left) || RBNode::is_red(&node.right))
+                {
+                    return Err(Violation::RedRedEdge);
+                }
+                if RBNode::is_red(&node.right) {
+                    return Err(Violation::RightLeaningRed);
+                }
+
+                let (left, left_size) = left.unwrap_or((0, 0));
+                let (right, right_size) = right.unwrap_or((0, 0));
+                let size = 1 + left_size + right_size;
+                if node.size != size {
+                    return Err(Violation::StaleSize {
+                        cached: node.size,
+                        actual: size,
+                    });
+                }
+                if left != right {
+                    return Err(Violation::BlackHeightMismatch { left, right });
+                }
+                Ok((left + usize::from(node.color == Color::Black), size))
+            },
+        )?;
+        Ok(summary.map_or(0, |(black_height, _)| black_height))
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<&RBNode<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+
+    fn closest(&self, accept: impl Fn(&K) -> bool, from_below: bool) -> Option<(&K, &V)> {
+        let mut link = self.root.as_deref();
+        let mut best = None;
+        while let Some(node) = link {
+            let accepted = accept(&node.key);
+            if accepted {
+                best = Some((&node.key, &node.value));
+            }
+            link = if accepted == from_below {
+                node.right.as_deref()
+            } else {
+                node.left.as_deref()
+            };
+        }
+        best
+    }
+
+    fn count_less(&self, key: &K, inclusive: bool) -> usize {
+        let mut link = self.root.as_deref();
+        let mut count = 0;
+        while let Some(node) = link {
+            if node.key < *key || (inclusive && node.key == *key) {
+                count += RBNode::size(&node.left) + 1;
+                link = node.right.as_deref();
+            } else {
+                link = node.left.as_deref();
+            }
+        }
+        count
+    }
+
+    // Deleting from a 2-node root needs a red link to borrow from, so the
+    // root is temporarily coloured red when both of its children are black.
+    fn redden_root(root: &mut RBNode<K, V>) {
+        if !RBNode::is_red(&root.left) && !RBNode::is_red(&root.right) {
+            root.color = Color::Red;
+        }
+    }
+
+    fn set_root(&mut self, mut root: Link<K, V>) {
+        if let Some(ref mut root) = root {
+            root.color = Color::Black;
+        }
+        self.root = root;
+    }
+}
+
+impl<K, V> RbTree<K, V> {
+    pub fn len(&self) -> usize {
+        RBNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, including red ones.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), RBNode::children)
+    }
+
+    /// Mean number of nodes visited by a successful lookup.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref left) = node.left {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref right) = node.right {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the `k`-th smallest entry, counting from zero.
+    pub fn select(&self, mut k: usize) -> Option<(&K, &V)> {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            let left_size = RBNode::size(&node.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => link = node.left.as_deref(),
+                Ordering::Equal => return Some((&node.key, &node.value)),
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    link = node.right.as_deref();
+                }
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            front: Vec::new(),
+            back: Vec::new(),
+            remaining: self.len(),
+        };
+        Iter::push_left(&mut iter.front, &self.root);
+        Iter::push_right(&mut iter.back, &self.root);
+        iter
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        for (k, v) in self.iter() {
+            visit_fn(k, v);
+        }
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        for (k, v) in self.preorder_iter() {
+            visit_fn(k, v);
+        }
+    }
+
+    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        for (k, v) in self.postorder_iter() {
+            visit_fn(k, v);
+        }
+    }
+
+    pub fn preorder_iter(&self) -> PreorderIter<'_, K, V> {
+        PreorderIter {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    pub fn postorder_iter(&self) -> PostorderIter<'_, K, V> {
+        PostorderIter {
+            stack: self
+                .root
+                .as_deref()
+                .map(|n| (n, false))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    pub fn level_order_iter(&self) -> LevelOrderIter<'_, K, V> {
+        LevelOrderIter {
+            queue: self.root.as_deref().into_iter().collect(),
+        }
+    }
+}
+
+impl<K: fmt::Display, V> RbTree<K, V> {
+    /// Renders the keys as a Graphviz digraph. Set [`DotOptions::color`] to
+    /// fill each node with its colour.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &RBNode<K, V>| n.key.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            RBNode::children,
+            label,
+            |n| Some(n.color.name()),
+            options,
+        )
+    }
+}
+
+/// Red nodes are marked with `*`.
+impl<K: fmt::Display, V> fmt::Display for RbTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &RBNode<K, V>| match n.color {
+            Color::Red => format!("{}*", n.key),
+            Color::Black => n.key.to_string(),
+        };
+        render::write_ascii(f, self.root.as_deref(), RBNode::children, label)
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    front: Vec<&'a RBNode<K, V>>,
+    back: Vec<&'a RBNode<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(stack: &mut Vec<&'a RBNode<K, V>>, mut link: &'a Link<K, V>) {
+        while let Some(n) = link {
+            let node: &RBNode<K, V> = n;
+            stack.push(node);
+            link = &node.left;
+        }
+    }
+
+    fn push_right(stack: &mut Vec<&'a RBNode<K, V>>, mut link: &'a Link<K, V>) {
+        while let Some(n) = link {
+            let node: &RBNode<K, V> = n;
+            stack.push(node);
+            link = &node.right;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.front.pop()?;
+        Self::push_left(&mut self.front, &node.right);
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.back.pop()?;
+        Self::push_right(&mut self.back, &node.left);
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+pub struct PreorderIter<'a, K, V> {
+    stack: Vec<&'a RBNode<K, V>>,
+}
+
+impl<'a, K, V> Iterator for PreorderIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if let Some(ref right) = node.right {
+            self.stack.push(right);
+        }
+        if let Some(ref left) = node.left {
+            self.stack.push(left);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> FusedIterator for PreorderIter<'_, K, V> {}
+
+pub struct PostorderIter<'a, K, V> {
+    stack: Vec<(&'a RBNode<K, V>, bool)>,
+}
+
+impl<'a, K, V> Iterator for PostorderIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some((&node.key, &node.value));
+            }
+
+            self.stack.push((node, true));
+            if let Some(ref right) = node.right {
+                self.stack.push((right, false));
+            }
+            if let Some(ref left) = node.left {
+                self.stack.push((left, false));
+            }
+        }
+    }
+}
+
+impl<K, V> FusedIterator for PostorderIter<'_, K, V> {}
+
+pub struct LevelOrderIter<'a, K, V> {
+    queue: VecDeque<&'a RBNode<K, V>>,
+}
+
+impl<'a, K, V> Iterator for LevelOrderIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        if let Some(ref left) = node.left {
+            self.queue.push_back(left);
+        }
+        if let Some(ref right) = node.right {
+            self.queue.push_back(right);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> FusedIterator for LevelOrderIter<'_, K, V> {}
+
+pub struct Range<'a, K, V> {
+    front: Vec<&'a RBNode<K, V>>,
+    back: Vec<&'a RBNode<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.front.pop()?;
+        if self.back.last().is_some_and(|last| ptr::eq(*last, node)) {
+            self.front.clear();
+            self.back.clear();
+        } else {
+            Iter::push_left(&mut self.front, &node.right);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Range<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.back.pop()?;
+        if self.front.last().is_some_and(|first| ptr::eq(*first, node)) {
+            self.front.clear();
+            self.back.clear();
+        } else {
+            Iter::push_right(&mut self.back, &node.left);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> FusedIterator for Range<'_, K, V> {}
+
+pub struct IntoIter<K, V> {
+    stack: Vec<Box<RBNode<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> IntoIter<K, V> {
+    fn push_left(&mut self, mut link: Link<K, V>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        let right = node.right.take();
+        self.push_left(right);
+        self.remaining -= 1;
+        Some(node.into_entry())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> IntoIterator for RbTree<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a RbTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for RbTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for RbTree<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Color, Link, RBNode, RbTree};
+
+    // Serialized as a sequence of `(key, value)` pairs in key order.
+    impl<K: Serialize, V: Serialize> Serialize for RbTree<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for RbTree<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+            if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+                return Err(de::Error::custom(
+                    "keys are not in strictly ascending order",
+                ));
+            }
+            let len = entries.len();
+            let black_height = (len + 1).ilog2();
+            Ok(Self {
+                root: build(&mut entries.into_iter(), len, black_height),
+            })
+        }
+    }
+
+    // Builds a tree of black height `black_height` from the next `n` entries,
+    // which must be in order. `n` has to fit a 2-3 tree of that height, so it
+    // lies between `2^h - 1` and `3^h - 1`; a node whose children cannot hold
+    // `n - 1` entries becomes a 3-node with a red left child.
+    fn build<K, V>(
+        entries: &mut impl Iterator<Item = (K, V)>,
+        n: usize,
+        black_height: u32,
+    ) -> Link<K, V> {
+        if n == 0 {
+            return None;
+        }
+
+        let child_height = black_height - 1;
+        let capacity = 3usize.saturating_pow(child_height) - 1;
+        let mut node = if n - 1 <= 2 * capacity {
+            let left = build(entries, (n - 1) / 2, child_height);
+            let (key, value) = entries.next()?;
+            let mut node = RBNode::new(key, value);
+            node.left = left;
+            node.right = build(entries, n - 1 - (n - 1) / 2, child_height);
+            node
+        } else {
+            let rest = n - 2;
+            let (first, second) = (rest / 3, (rest + 1) / 3);
+            let left = build(entries, first, child_height);
+            let (key, value) = entries.next()?;
+            let mut red = RBNode::new(key, value);
+            red.left = left;
+            red.right = build(entries, second, child_height);
+            red.update_size();
+
+            let (key, value) = entries.next()?;
+            let mut node = RBNode::new(key, value);
+            node.left = Some(red);
+            node.right = build(entries, rest - first - second, child_height);
+            node
+        };
+        node.color = Color::Black;
+        node.update_size();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    fn sample_tree() -> RbTree<i32, String> {
+        [5, 3, 7, 2, 4, 6, 8]
+            .into_iter()
+            .map(|k| (k, k.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut tree = RbTree::new();
+        assert_eq!(tree.insert(5, "five"), None);
+        assert_eq!(tree.insert(3, "three"), None);
+        assert_eq!(tree.insert(5, "FIVE"), Some("five"));
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.get(&5), Some(&"FIVE"));
+        assert_eq!(tree.get(&3), Some(&"three"));
+        assert_eq!(tree.get(&4), None);
+        assert!(tree.contains_key(&3));
+        assert_eq!(tree.black_height(), Ok(1));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut tree = sample_tree();
+        tree.get_mut(&4).unwrap().push('!');
+        assert_eq!(tree.get(&4).map(String::as_str), Some("4!"));
+        assert!(tree.get_mut(&10).is_none());
+    }
+
+    #[test]
+    fn test_borrowed_key_lookup() {
+        let mut tree: RbTree<String, usize> = ["apple", "fig", "pear"]
+            .into_iter()
+            .map(|k| (k.to_string(), k.len()))
+            .collect();
+        assert_eq!(tree.get("fig"), Some(&3));
+        assert!(tree.contains_key("pear"));
+        *tree.get_mut("apple").unwrap() += 1;
+        assert_eq!(tree.remove("apple"), Some(("apple".to_string(), 6)));
+        assert!(!tree.contains_key("apple"));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_sorted_insert_stays_balanced() {
+        let mut tree = RbTree::new();
+        for k in 0..1024 {
+            tree.insert(k, ());
+            assert_eq!(tree.validate(), Ok(()));
+        }
+
+        assert!(tree.height() <= 20);
+        assert_eq!(tree.root.as_ref().unwrap().color, Color::Black);
+        assert_eq!(
+            tree.keys().copied().collect::<Vec<_>>(),
+            (0..1024).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.remove(&5), Some((5, "5".to_string())));
+        assert_eq!(tree.remove(&5), None);
+        assert_eq!(tree.remove(&2), Some((2, "2".to_string())));
+        assert_eq!(tree.len(), 5);
+        assert_eq!(
+            tree.keys().copied().collect::<Vec<_>>(),
+            vec![3, 4, 6, 7, 8]
+        );
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut tree: RbTree<i32, i32> = (0..100).map(|k| (k, k)).collect();
+        for k in (0..100).rev() {
+            assert_eq!(tree.remove(&k), Some((k, k)));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.remove(&0), None);
+    }
+
+    #[test]
+    fn test_pop_min_and_max() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.pop_min().map(|(k, _)| k), Some(2));
+        assert_eq!(tree.pop_max().map(|(k, _)| k), Some(8));
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.min().map(|(k, _)| *k), Some(3));
+        assert_eq!(tree.max().map(|(k, _)| *k), Some(7));
+    }
+
+    #[test]
+    fn test_navigation() {
+        let tree: RbTree<i32, ()> = [50, 20, 80, 10, 30, 70, 90]
+            .into_iter()
+            .map(|k| (k, ()))
+            .collect();
+        assert_eq!(tree.floor(&35).map(|(k, _)| *k), Some(30));
+        assert_eq!(tree.ceiling(&35).map(|(k, _)| *k), Some(50));
+        assert_eq!(tree.predecessor(&50).map(|(k, _)| *k), Some(30));
+        assert_eq!(tree.successor(&50).map(|(k, _)| *k), Some(70));
+        assert_eq!(tree.floor(&5), None);
+        assert_eq!(tree.ceiling(&95), None);
+        assert_eq!(tree.select(3).map(|(k, _)| *k), Some(50));
+        assert_eq!(tree.rank(&55), 4);
+        assert_eq!(tree.count_range(20..=70), 4);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let tree = sample_tree();
+        assert_eq!(
+            tree.keys().copied().collect::<Vec<_>>(),
+            vec![2, 3, 4, 5, 6, 7, 8]
+        );
+        assert_eq!(
+            tree.keys().rev().copied().collect::<Vec<_>>(),
+            vec![8, 7, 6, 5, 4, 3, 2]
+        );
+        assert_eq!(tree.iter().len(), 7);
+
+        let range: Vec<i32> = tree.range(3..6).map(|(k, _)| *k).collect();
+        assert_eq!(range, vec![3, 4, 5]);
+        let range: Vec<i32> = tree.range(4..).rev().map(|(k, _)| *k).collect();
+        assert_eq!(range, vec![8, 7, 6, 5, 4]);
+        assert_eq!(tree.range(9..).count(), 0);
+
+        let owned: Vec<(i32, String)> = tree.into_iter().collect();
+        assert_eq!(owned[0], (2, "2".to_string()));
+        assert_eq!(owned.len(), 7);
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree: RbTree<i32, ()> = (1..=3).map(|k| (k, ())).collect();
+        let mut pre = vec![];
+        tree.preorder_traversal(&mut |k, _| pre.push(*k));
+        let mut post = vec![];
+        tree.postorder_traversal(&mut |k, _| post.push(*k));
+        let mut inorder = vec![];
+        tree.inorder_traversal(&mut |k, _| inorder.push(*k));
+
+        assert_eq!(pre, vec![2, 1, 3]);
+        assert_eq!(post, vec![1, 3, 2]);
+        assert_eq!(inorder, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_bst_named_methods() {
+        let mut tree: RbTree<i32, ()> = (1..=7).map(|k| (k, ())).collect();
+        let keys =
+            |iter: &mut dyn Iterator<Item = (&i32, &())>| iter.map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys(&mut tree.preorder_iter()), vec![4, 2, 1, 3, 6, 5, 7]);
+        assert_eq!(keys(&mut tree.postorder_iter()), vec![1, 3, 2, 5, 7, 6, 4]);
+        assert_eq!(
+            keys(&mut tree.level_order_iter()),
+            vec![4, 2, 6, 1, 3, 5, 7]
+        );
+
+        assert!(tree.search(5));
+        tree.delete(5);
+        tree.delete(42);
+        assert!(!tree.search(5));
+        assert_eq!(tree.len(), 6);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_invariant_checker_detects_violations() {
+        let mut tree: RbTree<i32, ()> = (1..=3).map(|k| (k, ())).collect();
+        assert_eq!(tree.black_height(), Ok(2));
+
+        tree.root.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.validate().unwrap_err().violation, Violation::RedRoot);
+
+        let root = tree.root.as_mut().unwrap();
+        root.color = Color::Black;
+        root.left.as_mut().unwrap().color = Color::Red;
+        assert_eq!(
+            tree.validate().unwrap_err().violation,
+            Violation::BlackHeightMismatch { left: 0, right: 1 }
+        );
+
+        let root = tree.root.as_mut().unwrap();
+        root.right.as_mut().unwrap().color = Color::Red;
+        assert_eq!(
+            tree.validate().unwrap_err().violation,
+            Violation::RightLeaningRed
+        );
+    }
+
+    #[test]
+    fn test_randomized_against_reference() {
+        let mut rng = Lcg(0xdecaf);
+        let mut tree = RbTree::new();
+        let mut reference = std::collections::BTreeMap::new();
+
+        for i in 0..5000 {
+            let key = (rng.next() % 300) as i32;
+            if rng.next().is_multiple_of(3) {
+                assert_eq!(tree.remove(&key), reference.remove_entry(&key));
+            } else {
+                assert_eq!(tree.insert(key, i), reference.insert(key, i));
+            }
+            assert_eq!(tree.validate(), Ok(()));
+        }
+
+        assert_eq!(tree.len(), reference.len());
+        assert!(tree.iter().eq(reference.iter()));
+    }
+
+    #[test]
+    fn test_validate_reports_path() {
+        let mut tree: RbTree<i32, ()> = (1..=7).map(|k| (k, ())).collect();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.depth_histogram(), vec![1, 2, 4]);
+        assert_eq!(tree.average_search_depth(), 17.0 / 7.0);
+
+        let right = tree.root.as_mut().unwrap().right.as_mut().unwrap();
+        right.left.as_mut().unwrap().size = 9;
+        let err = tree.validate().unwrap_err();
+        assert_eq!(err.path, "RL");
+        assert_eq!(
+            err.violation,
+            Violation::StaleSize {
+                cached: 9,
+                actual: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_colors() {
+        let tree: RbTree<i32, ()> = [(1, ()), (2, ())].into_iter().collect();
+        assert_eq!(tree.to_string(), "2\n|-- 1*\n`-- nil\n");
+
+        let plain = tree.to_dot();
+        assert!(!plain.contains("fillcolor"));
+        let colored = tree.to_dot_with(DotOptions {
+            color: true,
+            ..DotOptions::default()
+        });
+        assert!(
+            colored.contains("n0 [label=\"2\", style=filled, fillcolor=black, fontcolor=white];")
+        );
+        assert!(colored.contains("n1 [label=\"1\", style=filled, fillcolor=red, fontcolor=white];"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: RbTree<u32, char> = (0..26).map(|i| (i, (b'a' + i as u8) as char)).collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert!(json.starts_with(r#"[[0,"a"],[1,"b"]"#));
+
+        let back: RbTree<u32, char> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(tree.iter()));
+        assert_eq!(back.validate(), Ok(()));
+
+        for len in 0..300 {
+            let keys: Vec<(u32, u32)> = (0..len).map(|k| (k, k)).collect();
+            let json = serde_json::to_string(&keys).unwrap();
+            let back: RbTree<u32, u32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(back.validate(), Ok(()));
+            assert!(back.keys().copied().eq(0..len));
+            assert!(back.height() <= 2 * (len as usize + 1).ilog2() as usize);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deserialize_rejects_unsorted_keys() {
+        assert!(serde_json::from_str::<RbTree<i32, i32>>("[[2,0],[1,0]]").is_err());
+        assert!(serde_json::from_str::<RbTree<i32, i32>>("[[1,0],[1,0]]").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! Graphviz and terminal renderings shared by the trees in this module.
+
+use std::fmt::{self, Write};
+
+/// Per-node annotations to include in [`to_dot`](crate::binary_trees::Bst::to_dot_with)
+/// output. Heights and balance factors are measured from the tree itself, so
+/// they show what the shape really is rather than what a node has cached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Height of the subtree rooted at the node, counted in nodes.
+    pub height: bool,
+    /// Left subtree height minus right subtree height.
+    pub balance: bool,
+    /// Number of nodes in the subtree rooted at the node.
+    pub size: bool,
+    /// Fill nodes with their colour, for trees that have one.
+    pub color: bool,
+}
+
+impl DotOptions {
+    pub fn all() -> Self {
+        Self {
+            height: true,
+            balance: true,
+            size: true,
+            color: true,
+        }
+    }
+}
+
+/// Renders a tree as a Graphviz digraph. Nodes are numbered in preorder, and
+/// an internal node with a single child gets a point-shaped marker in place
+/// of the missing one so left and right stay distinguishable.
+pub(crate) fn to_dot<'a, N>(
+    root: Option<&'a N>,
+    children: impl Fn(&'a N) -> (Option<&'a N>, Option<&'a N>),
+    label: impl Fn(&'a N) -> String,
+    color: impl Fn(&'a N) -> Option<&'static str>,
+    options: DotOptions,
+) -> String {
+    // Number the nodes in preorder and remember each one's child ids.
+    let mut nodes = Vec::new();
+    let mut links: Vec<[Option<usize>; 2]> = Vec::new();
+    let mut stack: Vec<(&N, Option<(usize, usize)>)> =
+        root.map(|n| (n, None)).into_iter().collect();
+    while let Some((node, parent)) = stack.pop() {
+        let id = nodes.len();
+        nodes.push(node);
+        links.push([None, None]);
+        if let Some((parent, side)) = parent {
+            links[parent][side] = Some(id);
+        }
+
+        let (left, right) = children(node);
+        stack.extend(right.map(|n| (n, Some((id, 1)))));
+        stack.extend(left.map(|n| (n, Some((id, 0)))));
+    }
+
+    // Children always get higher ids than their parents, so a reverse sweep
+    // sees every subtree before the node above it.
+    let mut heights = vec![0usize; nodes.len()];
+    let mut sizes = vec![0usize; nodes.len()];
+    for id in (0..nodes.len()).rev() {
+        let [left, right] = links[id].map(|child| child.map_or((0, 0), |c| (heights[c], sizes[c])));
+        heights[id] = 1 + left.0.max(right.0);
+        sizes[id] = 1 + left.1 + right.1;
+    }
+
+    let annotated = options.height || options.balance || options.size;
+    let mut out = String::from("digraph tree {\n");
+    if !nodes.is_empty() {
+        let shape = if annotated { "box" } else { "circle" };
+        let _ = writeln!(out, "    node [shape={shape}, fontname=\"monospace\"];");
+    }
+
+    for (id, node) in nodes.iter().enumerate() {
+        let mut text = escape(&label(node));
+        if annotated {
+            let [left, right] = links[id].map(|child| child.map_or(0, |c| heights[c]));
+            let mut notes = Vec::new();
+            if options.height {
+                notes.push(format!("h={}", heights[id]));
+            }
+            if options.balance {
+                notes.push(format!("b={}", left as isize - right as isize));
+            }
+            if options.size {
+                notes.push(format!("n={}", sizes[id]));
+            }
+            let _ = write!(text, "\\n{}", notes.join(" "));
+        }
+
+        let _ = write!(out, "    n{id} [label=\"{text}\"");
+        if let Some(fill) = color(node).filter(|_| options.color) {
+            let _ = write!(out, ", style=filled, fillcolor={fill}, fontcolor=white");
+        }
+        out.push_str("];\n");
+
+        if links[id] == [None, None] {
+            continue;
+        }
+        for (side, child) in links[id].iter().enumerate() {
+            match child {
+                Some(child) => {
+                    let _ = writeln!(out, "    n{id} -> n{child};");
+                }
+                None => {
+                    let _ = writeln!(out, "    nil{id}_{side} [shape=point];");
+                    let _ = writeln!(out, "    n{id} -> nil{id}_{side};");
+                }
+            }
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Draws a tree sideways, one node per line, with the left child listed
+/// before the right one. A missing child next to a present one is drawn as
+/// `nil`.
+pub(crate) fn write_ascii<'a, N>(
+    f: &mut fmt::Formatter<'_>,
+    root: Option<&'a N>,
+    children: impl Fn(&'a N) -> (Option<&'a N>, Option<&'a N>),
+    label: impl Fn(&'a N) -> String,
+) -> fmt::Result {
+    let root = match root {
+        Some(root) => root,
+        None => return writeln!(f, "(empty)"),
+    };
+
+    writeln!(f, "{}", label(root))?;
+    let mut stack = Vec::new();
+    push_children(&mut stack, children(root), String::new());
+    while let Some((node, prefix, last)) = stack.pop() {
+        let connector = if last { "`-- " } else { "|-- " };
+        match node {
+            Some(node) => {
+                writeln!(f, "{prefix}{connector}{}", label(node))?;
+                let indent = if last { "    " } else { "|   " };
+                push_children(&mut stack, children(node), format!("{prefix}{indent}"));
+            }
+            None => writeln!(f, "{prefix}{connector}nil")?,
+        }
+    }
+    Ok(())
+}
+
+type Pending<'a, N> = Vec<(Option<&'a N>, String, bool)>;
+
+fn push_children<'a, N>(
+    stack: &mut Pending<'a, N>,
+    (left, right): (Option<&'a N>, Option<&'a N>),
+    prefix: String,
+) {
+    if left.is_none() && right.is_none() {
+        return;
+    }
+    stack.push((right, prefix.clone(), true));
+    stack.push((left, prefix, false));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Node {
+        key: &'static str,
+        left: Option<Box<Node>>,
+        right: Option<Box<Node>>,
+    }
+
+    fn node(
+        key: &'static str,
+        left: Option<Box<Node>>,
+        right: Option<Box<Node>>,
+    ) -> Option<Box<Node>> {
+        Some(Box::new(Node { key, left, right }))
+    }
+
+    fn children(n: &Node) -> (Option<&Node>, Option<&Node>) {
+        (n.left.as_deref(), n.right.as_deref())
+    }
+
+    struct Ascii(Option<Box<Node>>);
+
+    impl fmt::Display for Ascii {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write_ascii(f, self.0.as_deref(), children, |n| n.key.to_string())
+        }
+    }
+
+    #[test]
+    fn test_ascii_layout() {
+        let tree = node(
+            "d",
+            node("b", node("a", None, None), node("c", None, None)),
+            node("f", None, node("g", None, None)),
+        );
+        let expected = "\
+d
+|-- b
+|   |-- a
+|   `-- c
+`-- f
+    |-- nil
+    `-- g
+";
+        assert_eq!(Ascii(tree).to_string(), expected);
+        assert_eq!(Ascii(None).to_string(), "(empty)\n");
+    }
+
+    #[test]
+    fn test_dot_plain() {
+        let tree = node("b", node("a", None, None), None);
+        let dot = to_dot(
+            tree.as_deref(),
+            children,
+            |n| n.key.to_string(),
+            |_| None,
+            DotOptions::default(),
+        );
+        let expected = "\
+digraph tree {
+    node [shape=circle, fontname=\"monospace\"];
+    n0 [label=\"b\"];
+    n0 -> n1;
+    nil0_1 [shape=point];
+    n0 -> nil0_1;
+    n1 [label=\"a\"];
+}
+";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn test_dot_annotations_and_escaping() {
+        let tree = node("\"x\"", None, node("y", None, node("z", None, None)));
+        let dot = to_dot(
+            tree.as_deref(),
+            children,
+            |n| n.key.to_string(),
+            |n| (n.key == "y").then_some("red"),
+            DotOptions::all(),
+        );
+        assert!(dot.contains("n0 [label=\"\\\"x\\\"\\nh=3 b=-2 n=3\"];"));
+        assert!(dot.contains(
+            "n1 [label=\"y\\nh=2 b=-1 n=2\", style=filled, fillcolor=red, fontcolor=white];"
+        ));
+        assert!(dot.contains("nil1_0 [shape=point];"));
+        assert!(!dot.contains("nil2_"));
+        assert_eq!(
+            to_dot::<Node>(
+                None,
+                children,
+                |_| String::new(),
+                |_| None,
+                DotOptions::all()
+            ),
+            "digraph tree {\n}\n"
+        );
+    }
+}
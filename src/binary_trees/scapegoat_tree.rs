@@ -0,0 +1,845 @@
+use std::{cmp::Ordering, fmt, iter::FusedIterator, ops::RangeBounds, ptr};
+
+use super::{
+    bst::{above_start, below_end, partial_order},
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError},
+};
+
+type Link<T> = Option<Box<ScapegoatNode<T>>>;
+
+pub(crate) struct ScapegoatNode<T> {
+    left: Link<T>,
+    right: Link<T>,
+    data: T,
+}
+
+/// A scapegoat tree: nodes store nothing but their two links and element.
+/// When an insertion lands deeper than `log(n) / log(1 / alpha)`, the
+/// subtree rooted at the lowest ancestor whose child holds more than `alpha`
+/// of its nodes is rebuilt perfectly balanced, and the whole tree is rebuilt
+/// once deletions shrink it below `alpha` of its largest size.
+///
+/// Smaller `alpha` keeps the tree shallower at the cost of more frequent
+/// rebuilds. Every operation is `O(log n)` amortized.
+pub struct ScapegoatTree<T> {
+    root: Link<T>,
+    len: usize,
+    // The largest `len` since the whole tree was last rebuilt.
+    max_len: usize,
+    alpha: f64,
+}
+
+impl<T> ScapegoatNode<T> {
+    fn new(data: T) -> Box<Self> {
+        Box::new(ScapegoatNode {
+            left: None,
+            right: None,
+            data,
+        })
+    }
+
+    fn parts(&self) -> (&T, Option<&Self>, Option<&Self>) {
+        (&self.data, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn count(link: &Link<T>) -> usize {
+        let mut count = 0;
+        let mut stack: Vec<&Self> = link.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.left.as_deref());
+            stack.extend(node.right.as_deref());
+        }
+        count
+    }
+
+    /// Replaces the subtree below `link` with a perfectly balanced one made
+    /// of the same nodes.
+    fn rebuild(link: &mut Link<T>) {
+        let mut nodes = Vec::new();
+        let mut stack = Vec::new();
+        let mut curr = link.take();
+        loop {
+            while let Some(mut node) = curr {
+                curr = node.left.take();
+                stack.push(node);
+            }
+            let Some(mut node) = stack.pop() else { break };
+            curr = node.right.take();
+            nodes.push(node);
+        }
+
+        let len = nodes.len();
+        *link = Self::build(&mut nodes.into_iter(), len);
+    }
+
+    fn build(nodes: &mut impl Iterator<Item = Box<Self>>, n: usize) -> Link<T> {
+        if n == 0 {
+            return None;
+        }
+
+        let left = Self::build(nodes, n / 2);
+        let mut node = nodes.next()?;
+        node.left = left;
+        node.right = Self::build(nodes, n - n / 2 - 1);
+        Some(node)
+    }
+
+    fn take_min(mut link: &mut Link<T>) -> Option<Box<Self>> {
+        while link.as_ref()?.left.is_some() {
+            link = &mut link.as_mut()?.left;
+        }
+        let mut min = link.take()?;
+        *link = min.right.take();
+        Some(min)
+    }
+
+    fn take_max(mut link: &mut Link<T>) -> Option<Box<Self>> {
+        while link.as_ref()?.right.is_some() {
+            link = &mut link.as_mut()?.right;
+        }
+        let mut max = link.take()?;
+        *link = max.left.take();
+        Some(max)
+    }
+}
+
+impl<T: PartialOrd> Default for ScapegoatTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PartialOrd> ScapegoatTree<T> {
+    pub fn new() -> Self {
+        Self::with_alpha(0.7)
+    }
+
+    /// # Panics
+    ///
+    /// Panics unless `0.5 < alpha < 1`.
+    pub fn with_alpha(alpha: f64) -> Self {
+        assert!(
+            alpha > 0.5 && alpha < 1.0,
+            "alpha must lie strictly between 0.5 and 1"
+        );
+        Self {
+            root: None,
+            len: 0,
+            max_len: 0,
+            alpha,
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn insert(&mut self, val: T) {
+        // Directions taken from the root, `true` meaning right.
+        let mut path = Vec::new();
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            let right = partial_order(&val, &node.data) != Ordering::Less;
+            path.push(right);
+            link = if right {
+                &mut node.right
+            } else {
+                &mut node.left
+            };
+        }
+        *link = Some(ScapegoatNode::new(val));
+
+        self.len += 1;
+        self.max_len = self.max_len.max(self.len);
+        if path.len() > self.depth_limit() {
+            let depth = self.find_scapegoat(&path);
+            let mut link = &mut self.root;
+            for &right in &path[..depth] {
+                let node = link.as_mut().unwrap();
+                link = if right {
+                    &mut node.right
+                } else {
+                    &mut node.left
+                };
+            }
+            ScapegoatNode::rebuild(link);
+        }
+    }
+
+    pub fn search(&self, val: T) -> bool {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match partial_order(&val, &node.data) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    pub fn delete(&mut self, val: T) {
+        let mut link = &mut self.root;
+        loop {
+            let ord = match link.as_ref() {
+                Some(node) => partial_order(&val, &node.data),
+                None => return,
+            };
+            if ord == Ordering::Equal {
+                break;
+            }
+            let node = link.as_mut().unwrap();
+            link = if ord == Ordering::Less {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+
+        let mut node = link.take().unwrap();
+        *link = match (node.left.take(), node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(left), mut right) => {
+                let mut successor = ScapegoatNode::take_min(&mut right).unwrap();
+                successor.left = Some(left);
+                successor.right = right;
+                Some(successor)
+            }
+        };
+
+        self.after_removal();
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let min = ScapegoatNode::take_min(&mut self.root)?;
+        self.after_removal();
+        Some(min.data)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        let max = ScapegoatNode::take_max(&mut self.root)?;
+        self.after_removal();
+        Some(max.data)
+    }
+
+    // Rebuilds the whole tree once it has shrunk below `alpha` of its
+    // largest size.
+    fn after_removal(&mut self) {
+        self.len -= 1;
+        if (self.len as f64) < self.alpha * self.max_len as f64 {
+            ScapegoatNode::rebuild(&mut self.root);
+            self.max_len = self.len;
+        }
+    }
+
+    /// Largest element less than or equal to `val`.
+    pub fn floor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data <= *val {
+                best = Some(&node.data);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        best
+    }
+
+    /// Smallest element greater than or equal to `val`.
+    pub fn ceiling(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data >= *val {
+                best = Some(&node.data);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        best
+    }
+
+    /// Largest element strictly less than `val`.
+    pub fn predecessor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data < *val {
+                best = Some(&node.data);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        best
+    }
+
+    /// Smallest element strictly greater than `val`.
+    pub fn successor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data > *val {
+                best = Some(&node.data);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        best
+    }
+
+    /// Number of elements strictly less than `val`. Nodes cache no subtree
+    /// sizes, so this counts the smaller elements one by one.
+    pub fn rank(&self, val: &T) -> usize {
+        self.iter().take_while(|data| *data < val).count()
+    }
+
+    /// Like [`rank`](Self::rank), this walks every element it counts.
+    pub fn count_range<R: RangeBounds<T>>(&self, range: R) -> usize {
+        self.range(range).count()
+    }
+
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T> {
+        let mut stack = Vec::new();
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &ScapegoatNode<T> = n;
+            if above_start(&node.data, range.start_bound()) {
+                stack.push(node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+
+        let mut last = None;
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &ScapegoatNode<T> = n;
+            if below_end(&node.data, range.end_bound()) {
+                last = Some(node);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+
+        match (stack.last(), last) {
+            (Some(first), Some(last)) if first.data <= last.data => Range {
+                stack,
+                last: Some(last),
+            },
+            _ => Range {
+                stack: Vec::new(),
+                last: None,
+            },
+        }
+    }
+
+    /// Checks that every element is ordered against its ancestors.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            true,
+            ScapegoatNode::parts,
+            |_, _, _| Ok(()),
+        )
+        .map(|_| ())
+    }
+
+    // The deepest a node may sit before its insertion triggers a rebuild.
+    fn depth_limit(&self) -> usize {
+        ((self.len as f64).ln() / (1.0 / self.alpha).ln()).floor() as usize
+    }
+
+    /// Returns the depth of the lowest node on `path` with a child holding
+    /// more than `alpha` of its nodes. Such a node exists whenever the path
+    /// is longer than the depth limit.
+    fn find_scapegoat(&self, path: &[bool]) -> usize {
+        let mut ancestors = Vec::with_capacity(path.len());
+        let mut node = self.root.as_deref().unwrap();
+        for &right in path {
+            ancestors.push(node);
+            node = if right { &node.right } else { &node.left }
+                .as_deref()
+                .unwrap();
+        }
+
+        let mut child_size = 1;
+        for (depth, (&node, &right)) in ancestors.iter().zip(path).enumerate().rev() {
+            let sibling = if right { &node.left } else { &node.right };
+            let size = 1 + child_size + ScapegoatNode::count(sibling);
+            if child_size as f64 > self.alpha * size as f64 {
+                return depth;
+            }
+            child_size = size;
+        }
+        0
+    }
+}
+
+impl<T> ScapegoatTree<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of nodes on the longest root-to-leaf path.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), ScapegoatNode::children)
+    }
+
+    /// Mean number of nodes visited by a successful search.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(&node.data)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(&node.data)
+    }
+
+    /// The `k`-th smallest element, counting from zero. Like
+    /// [`rank`](Self::rank), this walks the elements before it.
+    pub fn select(&self, k: usize) -> Option<&T> {
+        self.iter().nth(k)
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        self.iter().for_each(visit_fn);
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        let mut stack: Vec<&ScapegoatNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visit_fn(&node.data);
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+        }
+    }
+
+    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        let mut stack: Vec<(&ScapegoatNode<T>, bool)> = self
+            .root
+            .as_deref()
+            .map(|n| (n, false))
+            .into_iter()
+            .collect();
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                visit_fn(&node.data);
+                continue;
+            }
+            stack.push((node, true));
+            stack.extend(node.right.as_deref().map(|n| (n, false)));
+            stack.extend(node.left.as_deref().map(|n| (n, false)));
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<T: fmt::Display> ScapegoatTree<T> {
+    /// Renders the tree as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &ScapegoatNode<T>| n.data.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            ScapegoatNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for ScapegoatTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &ScapegoatNode<T>| n.data.to_string();
+        render::write_ascii(f, self.root.as_deref(), ScapegoatNode::children, label)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ScapegoatTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    stack: Vec<&'a ScapegoatNode<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut link: &'a Link<T>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+pub struct Range<'a, T> {
+    stack: Vec<&'a ScapegoatNode<T>>,
+    // The largest node inside the range.
+    last: Option<&'a ScapegoatNode<T>>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if self.last.is_some_and(|last| ptr::eq(last, node)) {
+            self.stack.clear();
+        } else {
+            let mut link = &node.right;
+            while let Some(next) = link {
+                self.stack.push(next);
+                link = &next.left;
+            }
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> FusedIterator for Range<'_, T> {}
+
+pub struct IntoIter<T> {
+    stack: Vec<Box<ScapegoatNode<T>>>,
+    remaining: usize,
+}
+
+impl<T> IntoIter<T> {
+    fn push_left(&mut self, mut link: Link<T>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        self.remaining -= 1;
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for ScapegoatTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len,
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ScapegoatTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for ScapegoatTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for ScapegoatTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for val in iter {
+            self.insert(val);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::ScapegoatTree;
+
+    // Serialized as a sequence in ascending order. The alpha factor is not
+    // kept; deserialized trees use the default.
+    impl<T: Serialize> Serialize for ScapegoatTree<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T: Deserialize<'de> + PartialOrd> Deserialize<'de> for ScapegoatTree<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+    use std::{
+        collections::{BTreeMap, VecDeque},
+        ops::Bound,
+    };
+
+    fn height_bound(tree: &ScapegoatTree<u64>) -> usize {
+        let n = tree.max_len.max(1) as f64;
+        (n.ln() / (1.0 / tree.alpha).ln()).floor() as usize + 1
+    }
+
+    #[test]
+    fn test_basic_operations() {
+        let mut tree: ScapegoatTree<i32> = [5, 3, 8, 1, 4, 7, 9].into_iter().collect();
+        assert_eq!(tree.len(), 7);
+        assert!(tree.search(4));
+        assert!(!tree.search(6));
+        assert_eq!(tree.min(), Some(&1));
+        assert_eq!(tree.max(), Some(&9));
+        assert_eq!(tree.floor(&6), Some(&5));
+        assert_eq!(tree.ceiling(&6), Some(&7));
+
+        tree.delete(5);
+        tree.delete(42);
+        assert_eq!(tree.len(), 6);
+        assert!(tree.iter().copied().eq([1, 3, 4, 7, 8, 9]));
+        assert_eq!(format!("{tree:?}"), "{1, 3, 4, 7, 8, 9}");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_sorted_inserts_stay_shallow() {
+        for alpha in [0.55, 0.7, 0.9] {
+            let mut tree = ScapegoatTree::with_alpha(alpha);
+            for k in 0..2000u64 {
+                tree.insert(k);
+                assert!(tree.height() <= height_bound(&tree));
+            }
+            assert!(tree.iter().copied().eq(0..2000));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_random_against_btreemap() {
+        let mut rng = Lcg(19);
+        let mut tree = ScapegoatTree::new();
+        let mut reference: BTreeMap<u64, usize> = BTreeMap::new();
+
+        for i in 0..20_000 {
+            let key = rng.next() % 1000;
+            if rng.next() % 5 < 2 {
+                tree.delete(key);
+                if let Some(count) = reference.get_mut(&key) {
+                    *count -= 1;
+                    if *count == 0 {
+                        reference.remove(&key);
+                    }
+                }
+            } else {
+                tree.insert(key);
+                *reference.entry(key).or_default() += 1;
+            }
+            assert_eq!(tree.len(), reference.values().sum::<usize>());
+            if i % 100 == 0 {
+                assert!(tree.height() <= height_bound(&tree));
+            }
+        }
+
+        let expected = reference
+            .iter()
+            .flat_map(|(&k, &count)| std::iter::repeat_n(k, count));
+        assert!(tree.iter().copied().eq(expected));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_deletions_rebuild_whole_tree() {
+        let mut tree: ScapegoatTree<u64> = (0..1000).collect();
+        for k in 0..900 {
+            tree.delete(k);
+        }
+        assert_eq!(tree.len(), 100);
+        assert!(tree.max_len < 1000);
+        assert!(tree.height() <= height_bound(&tree));
+    }
+
+    #[test]
+    #[should_panic(expected = "alpha must lie strictly between 0.5 and 1")]
+    fn test_rejects_bad_alpha() {
+        ScapegoatTree::<i32>::with_alpha(0.5);
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree: ScapegoatTree<i32> = [2, 1, 3].into_iter().collect();
+        let mut seen = Vec::new();
+        tree.preorder_traversal(&mut |&x| seen.push(x));
+        assert_eq!(seen, [2, 1, 3]);
+        seen.clear();
+        tree.postorder_traversal(&mut |&x| seen.push(x));
+        assert_eq!(seen, [1, 3, 2]);
+        assert!(tree.into_iter().eq([1, 2, 3]));
+    }
+
+    #[test]
+    fn test_ordered_queries_against_sorted_vec() {
+        let mut rng = Lcg(31);
+        let mut sorted: Vec<u64> = (0..2000).map(|_| rng.next() % 500).collect();
+        let mut tree: ScapegoatTree<u64> = sorted.iter().copied().collect();
+        sorted.sort_unstable();
+
+        for _ in 0..500 {
+            let q = rng.next() % 520;
+            let d = rng.next() % 60;
+            let below = sorted.partition_point(|&x| x < q);
+            let above = sorted.partition_point(|&x| x <= q);
+            assert_eq!(
+                tree.predecessor(&q),
+                below.checked_sub(1).map(|i| &sorted[i])
+            );
+            assert_eq!(tree.successor(&q), sorted.get(above));
+            assert_eq!(tree.rank(&q), below);
+            assert_eq!(tree.select(tree.rank(&q)), sorted.get(below));
+
+            let expected: Vec<u64> = sorted
+                .iter()
+                .copied()
+                .filter(|x| (q..q + d).contains(x))
+                .collect();
+            assert!(tree.range(q..q + d).copied().eq(expected.iter().copied()));
+            assert_eq!(tree.count_range(q..q + d), expected.len());
+            assert_eq!(tree.count_range(..=q), above);
+            assert_eq!(
+                tree.count_range((Bound::Excluded(q), Bound::Included(q + d))),
+                sorted.iter().filter(|&&x| x > q && x <= q + d).count()
+            );
+        }
+        assert_eq!(tree.range(10..10).count(), 0);
+        assert_eq!(
+            tree.range((Bound::Included(400), Bound::Excluded(100)))
+                .count(),
+            0
+        );
+
+        let mut reference = VecDeque::from(sorted);
+        while !reference.is_empty() {
+            if rng.next().is_multiple_of(2) {
+                assert_eq!(tree.pop_min(), reference.pop_front());
+            } else {
+                assert_eq!(tree.pop_max(), reference.pop_back());
+            }
+            if reference.len() % 100 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+                assert!(tree.iter().eq(reference.iter()));
+            }
+        }
+        assert_eq!(tree.pop_min(), None);
+        assert_eq!(tree.pop_max(), None);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let tree: ScapegoatTree<i32> = [2, 1, 3].into_iter().collect();
+        assert_eq!(tree.to_string(), "2\n|-- 1\n`-- 3\n");
+        assert!(tree.to_dot().contains("n0 [label=\"2\"];"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: ScapegoatTree<i32> = [4, 2, 6, 2, 9].into_iter().collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[2,2,4,6,9]");
+
+        let back: ScapegoatTree<i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(tree.iter()));
+        assert_eq!(back.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,742 @@
+use std::{fmt, marker::PhantomData, mem, ops::RangeBounds};
+
+use crate::util::index_bounds;
+
+/// An associative operation with an identity element.
+pub trait Monoid {
+    type Value: Clone;
+
+    fn identity() -> Self::Value;
+    fn combine(a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// An update applied lazily to whole ranges of a [`SegmentTree`].
+///
+/// `apply` must distribute over [`Monoid::combine`], and `a.compose(&b)` must
+/// have the same effect as applying `b` and then `a`.
+pub trait Action<M: Monoid>: Clone {
+    fn identity() -> Self;
+    /// Applies the action to the combined value of `len` elements.
+    fn apply(&self, value: &M::Value, len: usize) -> M::Value;
+    fn compose(&self, earlier: &Self) -> Self;
+}
+
+/// No range updates, for trees that only need [`SegmentTree::set`].
+impl<M: Monoid> Action<M> for () {
+    fn identity() -> Self {}
+
+    fn apply(&self, value: &M::Value, _len: usize) -> M::Value {
+        value.clone()
+    }
+
+    fn compose(&self, _earlier: &Self) -> Self {}
+}
+
+/// A segment tree over the monoid `M`, with optional lazy range updates of
+/// type `A`. Nodes live in one flat array in heap order, with the leaves
+/// padded out to a power of two.
+pub struct SegmentTree<M: Monoid, A: Action<M> = ()> {
+    len: usize,
+    log: u32,
+    tree: Box<[M::Value]>,
+    // Pending actions for the internal nodes, already applied to the node
+    // itself but not yet to its children.
+    lazy: Box<[A]>,
+}
+
+impl<M: Monoid, A: Action<M>> SegmentTree<M, A> {
+    /// Creates a tree of `len` identity elements.
+    pub fn new(len: usize) -> Self {
+        Self::build(std::iter::repeat_n(M::identity(), len))
+    }
+
+    /// Builds a tree over `values` in linear time.
+    pub fn build<I: IntoIterator<Item = M::Value>>(values: I) -> Self {
+        let mut values: Vec<M::Value> = values.into_iter().collect();
+        let len = values.len();
+        let size = len.next_power_of_two();
+        let log = size.trailing_zeros();
+
+        let mut tree = vec![M::identity(); size];
+        tree.append(&mut values);
+        tree.resize(2 * size, M::identity());
+        for k in (1..size).rev() {
+            tree[k] = M::combine(&tree[2 * k], &tree[2 * k + 1]);
+        }
+
+        Self {
+            len,
+            log,
+            tree: tree.into_boxed_slice(),
+            lazy: vec![A::identity(); size].into_boxed_slice(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn get(&self, index: usize) -> M::Value {
+        assert!(index < self.len, "index out of bounds");
+        // Actions further up the tree are newer than those below them.
+        let leaf = index + self.size();
+        (1..=self.log).fold(self.tree[leaf].clone(), |value, i| {
+            self.lazy[leaf >> i].apply(&value, 1)
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn set(&mut self, index: usize, value: M::Value) {
+        assert!(index < self.len, "index out of bounds");
+        let leaf = index + self.size();
+        for i in (1..=self.log).rev() {
+            self.push(leaf >> i);
+        }
+        self.tree[leaf] = value;
+        for i in 1..=self.log {
+            self.pull(leaf >> i);
+        }
+    }
+
+    /// Combines every element in `range`. Takes `&mut self` because pending
+    /// updates on the boundary paths are pushed down on the way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn query<R: RangeBounds<usize>>(&mut self, range: R) -> M::Value {
+        let (start, end) = index_bounds(range, self.len);
+        if start == end {
+            return M::identity();
+        }
+
+        let (mut l, mut r) = (start + self.size(), end + self.size());
+        self.push_boundaries(l, r);
+        let (mut left, mut right) = (M::identity(), M::identity());
+        while l < r {
+            if l & 1 == 1 {
+                left = M::combine(&left, &self.tree[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                right = M::combine(&self.tree[r], &right);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        M::combine(&left, &right)
+    }
+
+    /// Combines every element.
+    pub fn all(&self) -> M::Value {
+        self.tree[1].clone()
+    }
+
+    /// Applies `action` to every element in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn update<R: RangeBounds<usize>>(&mut self, range: R, action: A) {
+        let (start, end) = index_bounds(range, self.len);
+        if start == end {
+            return;
+        }
+
+        let (l, r) = (start + self.size(), end + self.size());
+        self.push_boundaries(l, r);
+        let (mut lo, mut hi) = (l, r);
+        while lo < hi {
+            if lo & 1 == 1 {
+                self.apply(lo, &action);
+                lo += 1;
+            }
+            if hi & 1 == 1 {
+                hi -= 1;
+                self.apply(hi, &action);
+            }
+            lo >>= 1;
+            hi >>= 1;
+        }
+
+        for i in 1..=self.log {
+            if (l >> i) << i != l {
+                self.pull(l >> i);
+            }
+            if (r >> i) << i != r {
+                self.pull((r - 1) >> i);
+            }
+        }
+    }
+
+    /// Returns the largest `end` such that `pred` holds for the combination
+    /// of `start..end`. `pred` must hold for the identity and, once false,
+    /// stay false as the range grows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > self.len()` or `pred` rejects the identity.
+    pub fn max_right(&mut self, start: usize, pred: impl Fn(&M::Value) -> bool) -> usize {
+        assert!(start <= self.len, "index out of bounds");
+        assert!(pred(&M::identity()), "predicate must hold for the identity");
+        if start == self.len {
+            return self.len;
+        }
+
+        let size = self.size();
+        let mut l = start + size;
+        for i in (1..=self.log).rev() {
+            self.push(l >> i);
+        }
+
+        let mut acc = M::identity();
+        loop {
+            l >>= l.trailing_zeros();
+            let next = M::combine(&acc, &self.tree[l]);
+            if !pred(&next) {
+                // Descend towards the first element that breaks `pred`.
+                while l < size {
+                    self.push(l);
+                    l *= 2;
+                    let next = M::combine(&acc, &self.tree[l]);
+                    if pred(&next) {
+                        acc = next;
+                        l += 1;
+                    }
+                }
+                return l - size;
+            }
+            acc = next;
+            l += 1;
+            if l.is_power_of_two() {
+                return self.len;
+            }
+        }
+    }
+
+    /// Returns the smallest `start` such that `pred` holds for the
+    /// combination of `start..end`, under the same conditions as
+    /// [`max_right`](Self::max_right).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()` or `pred` rejects the identity.
+    pub fn min_left(&mut self, end: usize, pred: impl Fn(&M::Value) -> bool) -> usize {
+        assert!(end <= self.len, "index out of bounds");
+        assert!(pred(&M::identity()), "predicate must hold for the identity");
+        if end == 0 {
+            return 0;
+        }
+
+        let size = self.size();
+        let mut r = end + size;
+        for i in (1..=self.log).rev() {
+            self.push((r - 1) >> i);
+        }
+
+        let mut acc = M::identity();
+        loop {
+            r -= 1;
+            while r > 1 && r & 1 == 1 {
+                r >>= 1;
+            }
+            let next = M::combine(&self.tree[r], &acc);
+            if !pred(&next) {
+                while r < size {
+                    self.push(r);
+                    r = 2 * r + 1;
+                    let next = M::combine(&self.tree[r], &acc);
+                    if pred(&next) {
+                        acc = next;
+                        r -= 1;
+                    }
+                }
+                return r + 1 - size;
+            }
+            acc = next;
+            if r.is_power_of_two() {
+                return 0;
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.lazy.len()
+    }
+
+    fn pull(&mut self, k: usize) {
+        self.tree[k] = M::combine(&self.tree[2 * k], &self.tree[2 * k + 1]);
+    }
+
+    // Padding leaves are never inside an updated range, so a node that
+    // receives an action always covers exactly `len` real elements.
+    fn apply(&mut self, k: usize, action: &A) {
+        let len = self.size() >> k.ilog2();
+        self.tree[k] = action.apply(&self.tree[k], len);
+        if k < self.size() {
+            self.lazy[k] = action.compose(&self.lazy[k]);
+        }
+    }
+
+    fn push(&mut self, k: usize) {
+        let action = mem::replace(&mut self.lazy[k], A::identity());
+        self.apply(2 * k, &action);
+        self.apply(2 * k + 1, &action);
+    }
+
+    // Pushes pending actions down the paths to leaves `l` and `r - 1`, except
+    // into nodes that lie entirely inside `l..r`.
+    fn push_boundaries(&mut self, l: usize, r: usize) {
+        for i in (1..=self.log).rev() {
+            if (l >> i) << i != l {
+                self.push(l >> i);
+            }
+            if (r >> i) << i != r {
+                self.push((r - 1) >> i);
+            }
+        }
+    }
+}
+
+impl<M: Monoid, A: Action<M>> FromIterator<M::Value> for SegmentTree<M, A> {
+    fn from_iter<I: IntoIterator<Item = M::Value>>(iter: I) -> Self {
+        Self::build(iter)
+    }
+}
+
+impl<M: Monoid, A: Action<M>> fmt::Debug for SegmentTree<M, A>
+where
+    M::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.len).map(|i| self.get(i)))
+            .finish()
+    }
+}
+
+/// Addition, with zero as the identity.
+#[derive(Debug, Clone, Copy)]
+pub struct Sum<T>(PhantomData<T>);
+
+/// Minimum, with the type's largest value as the identity.
+#[derive(Debug, Clone, Copy)]
+pub struct Min<T>(PhantomData<T>);
+
+/// Maximum, with the type's smallest value as the identity.
+#[derive(Debug, Clone, Copy)]
+pub struct Max<T>(PhantomData<T>);
+
+/// Greatest common divisor, with zero as the identity.
+#[derive(Debug, Clone, Copy)]
+pub struct Gcd<T>(PhantomData<T>);
+
+/// Adds a constant to every element of a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeAdd<T>(pub T);
+
+/// Overwrites every element of a range. `RangeAssign(None)` leaves the range
+/// unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeAssign<T>(pub Option<T>);
+
+macro_rules! integer_monoids {
+    ($($t:ty),*) => {$(
+        impl Monoid for Sum<$t> {
+            type Value = $t;
+
+            fn identity() -> $t {
+                0
+            }
+
+            fn combine(a: &$t, b: &$t) -> $t {
+                a + b
+            }
+        }
+
+        impl Monoid for Min<$t> {
+            type Value = $t;
+
+            fn identity() -> $t {
+                <$t>::MAX
+            }
+
+            fn combine(a: &$t, b: &$t) -> $t {
+                *a.min(b)
+            }
+        }
+
+        impl Monoid for Max<$t> {
+            type Value = $t;
+
+            fn identity() -> $t {
+                <$t>::MIN
+            }
+
+            fn combine(a: &$t, b: &$t) -> $t {
+                *a.max(b)
+            }
+        }
+
+        impl Action<Sum<$t>> for RangeAdd<$t> {
+            fn identity() -> Self {
+                RangeAdd(0)
+            }
+
+            fn apply(&self, value: &$t, len: usize) -> $t {
+                value + self.0 * len as $t
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAdd(self.0 + earlier.0)
+            }
+        }
+
+        impl Action<Min<$t>> for RangeAdd<$t> {
+            fn identity() -> Self {
+                RangeAdd(0)
+            }
+
+            fn apply(&self, value: &$t, _len: usize) -> $t {
+                value + self.0
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAdd(self.0 + earlier.0)
+            }
+        }
+
+        impl Action<Max<$t>> for RangeAdd<$t> {
+            fn identity() -> Self {
+                RangeAdd(0)
+            }
+
+            fn apply(&self, value: &$t, _len: usize) -> $t {
+                value + self.0
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAdd(self.0 + earlier.0)
+            }
+        }
+
+        impl Action<Sum<$t>> for RangeAssign<$t> {
+            fn identity() -> Self {
+                RangeAssign(None)
+            }
+
+            fn apply(&self, value: &$t, len: usize) -> $t {
+                self.0.map_or(*value, |x| x * len as $t)
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAssign(self.0.or(earlier.0))
+            }
+        }
+
+        impl Action<Min<$t>> for RangeAssign<$t> {
+            fn identity() -> Self {
+                RangeAssign(None)
+            }
+
+            fn apply(&self, value: &$t, _len: usize) -> $t {
+                self.0.unwrap_or(*value)
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAssign(self.0.or(earlier.0))
+            }
+        }
+
+        impl Action<Max<$t>> for RangeAssign<$t> {
+            fn identity() -> Self {
+                RangeAssign(None)
+            }
+
+            fn apply(&self, value: &$t, _len: usize) -> $t {
+                self.0.unwrap_or(*value)
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAssign(self.0.or(earlier.0))
+            }
+        }
+    )*};
+}
+
+integer_monoids!(i32, i64, i128, isize, u32, u64, u128, usize);
+
+macro_rules! gcd_monoids {
+    ($($t:ty),*) => {$(
+        impl Monoid for Gcd<$t> {
+            type Value = $t;
+
+            fn identity() -> $t {
+                0
+            }
+
+            fn combine(a: &$t, b: &$t) -> $t {
+                let (mut a, mut b) = (*a, *b);
+                while b != 0 {
+                    (a, b) = (b, a % b);
+                }
+                a
+            }
+        }
+
+        impl Action<Gcd<$t>> for RangeAssign<$t> {
+            fn identity() -> Self {
+                RangeAssign(None)
+            }
+
+            fn apply(&self, value: &$t, _len: usize) -> $t {
+                self.0.unwrap_or(*value)
+            }
+
+            fn compose(&self, earlier: &Self) -> Self {
+                RangeAssign(self.0.or(earlier.0))
+            }
+        }
+    )*};
+}
+
+gcd_monoids!(u32, u64, u128, usize);
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Action, Monoid, SegmentTree};
+
+    // Serialized as the sequence of element values, with pending actions
+    // applied; internal aggregates are rebuilt on deserialization.
+    impl<M: Monoid, A: Action<M>> Serialize for SegmentTree<M, A>
+    where
+        M::Value: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..self.len()).map(|i| self.get(i)))
+        }
+    }
+
+    impl<'de, M: Monoid, A: Action<M>> Deserialize<'de> for SegmentTree<M, A>
+    where
+        M::Value: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Self::build(Vec::<M::Value>::deserialize(deserializer)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    // String concatenation, to catch operands combined in the wrong order.
+    struct Concat;
+
+    impl Monoid for Concat {
+        type Value = String;
+
+        fn identity() -> String {
+            String::new()
+        }
+
+        fn combine(a: &String, b: &String) -> String {
+            format!("{a}{b}")
+        }
+    }
+
+    #[test]
+    fn test_build_query_and_set() {
+        let mut tree: SegmentTree<Concat> = "abcdefg".chars().map(String::from).collect();
+        assert_eq!(tree.len(), 7);
+        assert_eq!(tree.all(), "abcdefg");
+        assert_eq!(tree.query(1..5), "bcde");
+        assert_eq!(tree.query(..=2), "abc");
+        assert_eq!(tree.query(3..3), "");
+
+        tree.set(3, "X".to_string());
+        assert_eq!(tree.query(2..), "cXefg");
+        assert_eq!(tree.get(3), "X");
+        assert_eq!(
+            format!("{tree:?}"),
+            r#"["a", "b", "c", "X", "e", "f", "g"]"#
+        );
+    }
+
+    #[test]
+    fn test_empty_and_single() {
+        let mut empty: SegmentTree<Sum<i64>> = SegmentTree::new(0);
+        assert!(empty.is_empty());
+        assert_eq!(empty.query(..), 0);
+        assert_eq!(empty.max_right(0, |_| true), 0);
+        assert_eq!(empty.min_left(0, |_| true), 0);
+
+        let mut one: SegmentTree<Max<i32>, RangeAdd<i32>> = SegmentTree::build([5]);
+        one.update(.., RangeAdd(2));
+        assert_eq!(one.query(0..1), 7);
+        assert_eq!(one.get(0), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "range end out of bounds")]
+    fn test_query_out_of_bounds() {
+        let mut tree: SegmentTree<Sum<i32>> = SegmentTree::new(4);
+        tree.query(2..5);
+    }
+
+    #[test]
+    fn test_range_add_sum_against_brute_force() {
+        let mut rng = Lcg(16);
+        let len = 300;
+        let mut reference: Vec<i64> = (0..len).map(|_| rng.next() as i64 % 100).collect();
+        let mut tree: SegmentTree<Sum<i64>, RangeAdd<i64>> = reference.iter().copied().collect();
+
+        for _ in 0..3000 {
+            let (l, r) = rng.range(len);
+            match rng.next() % 3 {
+                0 => {
+                    let x = rng.next() as i64 % 21 - 10;
+                    tree.update(l..r, RangeAdd(x));
+                    reference[l..r].iter_mut().for_each(|v| *v += x);
+                }
+                1 => {
+                    let x = rng.next() as i64 % 100;
+                    tree.set(l.min(len - 1), x);
+                    reference[l.min(len - 1)] = x;
+                }
+                _ => assert_eq!(tree.query(l..r), reference[l..r].iter().sum::<i64>()),
+            }
+            let i = rng.next() as usize % len;
+            assert_eq!(tree.get(i), reference[i]);
+        }
+    }
+
+    #[test]
+    fn test_range_assign_min_max_against_brute_force() {
+        let mut rng = Lcg(61);
+        let len = 257;
+        let mut reference: Vec<i32> = (0..len).map(|_| rng.next() as i32 % 1000).collect();
+        let mut min: SegmentTree<Min<i32>, RangeAssign<i32>> = reference.iter().copied().collect();
+        let mut max: SegmentTree<Max<i32>, RangeAdd<i32>> = reference.iter().copied().collect();
+
+        for _ in 0..2000 {
+            let (l, r) = rng.range(len);
+            let x = rng.next() as i32 % 1000 - 500;
+            if rng.next().is_multiple_of(2) {
+                // Keep `max` in step by adding the difference element-wise.
+                for (i, v) in reference.iter_mut().enumerate().take(r).skip(l) {
+                    max.update(i..=i, RangeAdd(x - *v));
+                    *v = x;
+                }
+                min.update(l..r, RangeAssign(Some(x)));
+            } else {
+                max.update(l..r, RangeAdd(x));
+                for (i, v) in reference.iter_mut().enumerate().take(r).skip(l) {
+                    *v += x;
+                    min.update(i..=i, RangeAssign(Some(*v)));
+                }
+            }
+
+            let (l, r) = rng.range(len);
+            let slice = &reference[l..r];
+            assert_eq!(
+                min.query(l..r),
+                slice.iter().copied().min().unwrap_or(i32::MAX)
+            );
+            assert_eq!(
+                max.query(l..r),
+                slice.iter().copied().max().unwrap_or(i32::MIN)
+            );
+        }
+    }
+
+    #[test]
+    fn test_gcd() {
+        let mut tree: SegmentTree<Gcd<u64>, RangeAssign<u64>> =
+            SegmentTree::build([12, 18, 24, 36, 7]);
+        assert_eq!(tree.query(..4), 6);
+        assert_eq!(tree.query(..), 1);
+        tree.update(1..3, RangeAssign(Some(8)));
+        assert_eq!(tree.query(..4), 4);
+        assert_eq!(tree.query(1..3), 8);
+    }
+
+    #[test]
+    fn test_max_right_and_min_left_against_brute_force() {
+        let mut rng = Lcg(99);
+        let len = 200;
+        let mut reference: Vec<u64> = (0..len).map(|_| rng.next() % 10).collect();
+        let mut tree: SegmentTree<Sum<u64>, RangeAdd<u64>> = reference.iter().copied().collect();
+
+        for _ in 0..1000 {
+            let (l, r) = rng.range(len);
+            let x = rng.next() % 5;
+            tree.update(l..r, RangeAdd(x));
+            reference[l..r].iter_mut().for_each(|v| *v += x);
+
+            let limit = rng.next() % 400;
+            let start = rng.next() as usize % (len + 1);
+            let mut end = start;
+            let mut sum = 0;
+            while end < len && sum + reference[end] <= limit {
+                sum += reference[end];
+                end += 1;
+            }
+            assert_eq!(tree.max_right(start, |&s| s <= limit), end);
+
+            let end = rng.next() as usize % (len + 1);
+            let mut start = end;
+            let mut sum = 0;
+            while start > 0 && sum + reference[start - 1] <= limit {
+                sum += reference[start - 1];
+                start -= 1;
+            }
+            assert_eq!(tree.min_left(end, |&s| s <= limit), start);
+        }
+    }
+
+    #[test]
+    fn test_binary_search_respects_order() {
+        let mut tree: SegmentTree<Concat> = "abcdefgh".chars().map(String::from).collect();
+        assert_eq!(tree.max_right(2, |s| !s.contains('f')), 5);
+        assert_eq!(tree.min_left(6, |s| !s.contains('b')), 2);
+        assert_eq!(tree.max_right(0, |s| s.len() < 100), 8);
+        assert_eq!(tree.min_left(8, |s| s.len() < 100), 0);
+    }
+
+    #[test]
+    fn test_large_build() {
+        let len = 1 << 20;
+        let mut tree: SegmentTree<Sum<u64>, RangeAdd<u64>> = (0..len as u64).collect();
+        tree.update(.., RangeAdd(1));
+        assert_eq!(tree.all(), (1..=len as u64).sum::<u64>());
+        assert_eq!(tree.query(len - 3..), 3 * len as u64 - 3);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut tree: SegmentTree<Sum<i64>, RangeAdd<i64>> = (1..=5).collect();
+        tree.update(1..4, RangeAdd(10));
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[1,12,13,14,5]");
+
+        let mut back: SegmentTree<Sum<i64>, RangeAdd<i64>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.all(), 45);
+        assert_eq!(back.query(2..), 32);
+    }
+}
@@ -0,0 +1,802 @@
+use std::{cmp::Ordering, fmt, iter::FusedIterator, mem};
+
+use super::{
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError},
+};
+
+type Link<K, V> = Option<Box<SplayNode<K, V>>>;
+
+pub(crate) struct SplayNode<K, V> {
+    left: Link<K, V>,
+    right: Link<K, V>,
+    key: K,
+    value: V,
+    size: usize,
+}
+
+/// A self-adjusting binary search tree. Every access splays the touched key
+/// to the root, so any sequence of `m` operations costs `O(m log n)` and
+/// recently used keys stay cheap to reach again.
+pub struct SplayTree<K, V> {
+    root: Link<K, V>,
+}
+
+impl<K, V> SplayNode<K, V> {
+    fn new(key: K, value: V) -> Box<Self> {
+        Box::new(SplayNode {
+            left: None,
+            right: None,
+            key,
+            value,
+            size: 1,
+        })
+    }
+
+    fn parts(&self) -> (&K, Option<&Self>, Option<&Self>) {
+        (&self.key, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn into_entry(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
+    fn size(link: &Link<K, V>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    /// Top-down splay: walks from `root` towards the key `cmp` is looking
+    /// for, peeling the nodes it passes into a left tree (smaller keys) and a
+    /// right tree (larger keys), then reassembles them under the last node
+    /// reached. `cmp` orders the probe against a node's key.
+    fn splay(root: Box<Self>, cmp: impl Fn(&K) -> Ordering) -> Box<Self> {
+        let mut smaller = Vec::new();
+        let mut larger = Vec::new();
+        let mut curr = root;
+
+        loop {
+            match cmp(&curr.key) {
+                Ordering::Less => {
+                    let mut left = match curr.left.take() {
+                        Some(left) => left,
+                        None => break,
+                    };
+                    if cmp(&left.key) == Ordering::Less {
+                        curr.left = left.right.take();
+                        curr.update_size();
+                        left.right = Some(curr);
+                        curr = left;
+                        let next = match curr.left.take() {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        larger.push(mem::replace(&mut curr, next));
+                    } else {
+                        larger.push(mem::replace(&mut curr, left));
+                    }
+                }
+                Ordering::Greater => {
+                    let mut right = match curr.right.take() {
+                        Some(right) => right,
+                        None => break,
+                    };
+                    if cmp(&right.key) == Ordering::Greater {
+                        curr.right = right.left.take();
+                        curr.update_size();
+                        right.left = Some(curr);
+                        curr = right;
+                        let next = match curr.right.take() {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        smaller.push(mem::replace(&mut curr, next));
+                    } else {
+                        smaller.push(mem::replace(&mut curr, right));
+                    }
+                }
+                Ordering::Equal => break,
+            }
+        }
+
+        // Nodes were peeled off top-down, so the deepest ones are linked in
+        // first and their sizes are final before their parents are updated.
+        let mut left = curr.left.take();
+        for mut node in smaller.into_iter().rev() {
+            node.right = left;
+            node.update_size();
+            left = Some(node);
+        }
+
+        let mut right = curr.right.take();
+        for mut node in larger.into_iter().rev() {
+            node.left = right;
+            node.update_size();
+            right = Some(node);
+        }
+
+        curr.left = left;
+        curr.right = right;
+        curr.update_size();
+        curr
+    }
+
+    /// Frees a subtree without recursing, rotating left children up the same
+    /// way as `BSTNode::drop_all`.
+    fn drop_all(mut link: Link<K, V>) {
+        while let Some(mut node) = link {
+            link = match node.left.take() {
+                Some(mut left) => {
+                    node.left = left.right.take();
+                    left.right = Some(node);
+                    Some(left)
+                }
+                None => node.right.take(),
+            };
+        }
+    }
+}
+
+impl<K: Ord, V> Default for SplayTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> SplayTree<K, V> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut root = match self.root.take() {
+            Some(root) => Self::splay_to(root, &key),
+            None => {
+                self.root = Some(SplayNode::new(key, value));
+                return None;
+            }
+        };
+
+        let mut node = SplayNode::new(key, value);
+        match node.key.cmp(&root.key) {
+            Ordering::Equal => {
+                let old = mem::replace(&mut root.value, node.value);
+                self.root = Some(root);
+                return Some(old);
+            }
+            Ordering::Less => {
+                node.left = root.left.take();
+                root.update_size();
+                node.right = Some(root);
+            }
+            Ordering::Greater => {
+                node.right = root.right.take();
+                root.update_size();
+                node.left = Some(root);
+            }
+        }
+        node.update_size();
+        self.root = Some(node);
+        None
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.splay(key).then(|| &self.root.as_ref().unwrap().value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if !self.splay(key) {
+            return None;
+        }
+        self.root.as_mut().map(|root| &mut root.value)
+    }
+
+    pub fn contains_key(&mut self, key: &K) -> bool {
+        self.splay(key)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        if !self.splay(key) {
+            return None;
+        }
+
+        let mut root = self.root.take().unwrap();
+        let right = root.right.take();
+        self.root = match root.left.take() {
+            // Every key on the left is smaller, so splaying for `key` brings
+            // the left subtree's maximum up with an empty right child.
+            Some(left) => {
+                let mut left = Self::splay_to(left, key);
+                left.right = right;
+                left.update_size();
+                Some(left)
+            }
+            None => right,
+        };
+        Some(root.into_entry())
+    }
+
+    /// Moves every entry with a key greater than or equal to `key` into a new
+    /// tree, leaving the smaller keys in `self`.
+    pub fn split(&mut self, key: &K) -> Self {
+        let mut root = match self.root.take() {
+            Some(root) => Self::splay_to(root, key),
+            None => return Self::new(),
+        };
+
+        if root.key < *key {
+            let right = root.right.take();
+            root.update_size();
+            self.root = Some(root);
+            Self { root: right }
+        } else {
+            self.root = root.left.take();
+            root.update_size();
+            Self { root: Some(root) }
+        }
+    }
+
+    /// Appends `other` to `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key in `other` is not greater than every key in `self`.
+    pub fn join(&mut self, mut other: Self) {
+        let other_root = match other.root.take() {
+            Some(root) => root,
+            None => return,
+        };
+        let mut root = match self.root.take() {
+            Some(root) => SplayNode::splay(root, |_| Ordering::Greater),
+            None => {
+                self.root = Some(other_root);
+                return;
+            }
+        };
+
+        let other_root = SplayNode::splay(other_root, |_| Ordering::Less);
+        assert!(
+            root.key < other_root.key,
+            "join requires every key in `other` to be greater than every key in `self`"
+        );
+        root.right = Some(other_root);
+        root.update_size();
+        self.root = Some(root);
+    }
+
+    /// Checks that keys are strictly ordered and that the cached subtree
+    /// sizes are correct.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            false,
+            SplayNode::parts,
+            |n, left, right| validate::check_size(n.size, left, right),
+        )
+        .map(|_| ())
+    }
+
+    // Splays `key` (or the last node on its search path) to the root and
+    // reports whether it is present.
+    fn splay(&mut self, key: &K) -> bool {
+        match self.root.take() {
+            Some(root) => {
+                let root = Self::splay_to(root, key);
+                let found = root.key == *key;
+                self.root = Some(root);
+                found
+            }
+            None => false,
+        }
+    }
+
+    fn splay_to(root: Box<SplayNode<K, V>>, key: &K) -> Box<SplayNode<K, V>> {
+        SplayNode::splay(root, |k| key.cmp(k))
+    }
+}
+
+impl<K, V> SplayTree<K, V> {
+    pub fn len(&self) -> usize {
+        SplayNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, so an empty tree has height zero.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), SplayNode::children)
+    }
+
+    /// Mean number of nodes a lookup visits before it splays, given the
+    /// current shape.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    /// The key currently at the root, i.e. the most recently accessed one.
+    pub fn root_key(&self) -> Option<&K> {
+        self.root.as_ref().map(|n| &n.key)
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref left) = node.left {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref right) = node.right {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        for (k, v) in self.iter() {
+            visit_fn(k, v);
+        }
+    }
+}
+
+impl<K: fmt::Display, V> SplayTree<K, V> {
+    /// Renders the keys as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &SplayNode<K, V>| n.key.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            SplayNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> fmt::Display for SplayTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &SplayNode<K, V>| n.key.to_string();
+        render::write_ascii(f, self.root.as_deref(), SplayNode::children, label)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for SplayTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a SplayNode<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(n) = link {
+            let node: &SplayNode<K, V> = n;
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+pub struct IntoIter<K, V> {
+    stack: Vec<Box<SplayNode<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> IntoIter<K, V> {
+    fn push_left(&mut self, mut link: Link<K, V>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        let right = node.right.take();
+        self.push_left(right);
+        self.remaining -= 1;
+        Some(node.into_entry())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> Drop for IntoIter<K, V> {
+    fn drop(&mut self) {
+        for node in self.stack.drain(..) {
+            SplayNode::drop_all(Some(node));
+        }
+    }
+}
+
+impl<K, V> IntoIterator for SplayTree<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<K, V> Drop for SplayTree<K, V> {
+    fn drop(&mut self) {
+        SplayNode::drop_all(self.root.take());
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SplayTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SplayTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for SplayTree<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Link, SplayNode, SplayTree};
+
+    // Serialized as a sequence of `(key, value)` pairs in key order; the shape left by past
+    // splays is not kept.
+    impl<K: Serialize, V: Serialize> Serialize for SplayTree<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for SplayTree<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+            if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+                return Err(de::Error::custom(
+                    "keys are not in strictly ascending order",
+                ));
+            }
+            let len = entries.len();
+            Ok(Self {
+                root: build(&mut entries.into_iter(), len),
+            })
+        }
+    }
+
+    // Builds a perfectly balanced tree from the next `n` entries, which must
+    // be in order.
+    fn build<K, V>(entries: &mut impl Iterator<Item = (K, V)>, n: usize) -> Link<K, V> {
+        if n == 0 {
+            return None;
+        }
+
+        let left = build(entries, n / 2);
+        let (key, value) = entries.next()?;
+        let mut node = SplayNode::new(key, value);
+        node.left = left;
+        node.right = build(entries, n - n / 2 - 1);
+        node.update_size();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    // Returns the subtree size after checking key order and cached sizes.
+    fn check_node(link: &Link<i32, i32>, lo: Option<i32>, hi: Option<i32>) -> usize {
+        match link {
+            Some(n) => {
+                assert!(lo.is_none_or(|lo| n.key > lo));
+                assert!(hi.is_none_or(|hi| n.key < hi));
+                let size = 1
+                    + check_node(&n.left, lo, Some(n.key))
+                    + check_node(&n.right, Some(n.key), hi);
+                assert_eq!(n.size, size);
+                size
+            }
+            None => 0,
+        }
+    }
+
+    fn keys(tree: &SplayTree<i32, i32>) -> Vec<i32> {
+        tree.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut tree = SplayTree::new();
+        assert_eq!(tree.insert(5, 50), None);
+        assert_eq!(tree.insert(3, 30), None);
+        assert_eq!(tree.insert(7, 70), None);
+        assert_eq!(tree.insert(3, 31), Some(30));
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&3), Some(&31));
+        assert_eq!(tree.get(&7), Some(&70));
+        assert_eq!(tree.get(&4), None);
+        check_node(&tree.root, None, None);
+    }
+
+    #[test]
+    fn test_access_moves_key_to_root() {
+        let mut tree: SplayTree<i32, i32> = (0..100).map(|k| (k, k)).collect();
+        tree.get(&42);
+        assert_eq!(tree.root_key(), Some(&42));
+        tree.insert(1000, 0);
+        assert_eq!(tree.root_key(), Some(&1000));
+        assert!(tree.contains_key(&17));
+        assert_eq!(tree.root_key(), Some(&17));
+        check_node(&tree.root, None, None);
+    }
+
+    #[test]
+    fn test_sequential_access_flattens_depth() {
+        // Inserting in order builds a path; a single access to the deepest
+        // key roughly halves the depth of every node on that path.
+        let mut tree: SplayTree<i32, i32> = (0..1024).map(|k| (k, k)).collect();
+        tree.get(&0);
+        fn depth(link: &Link<i32, i32>) -> usize {
+            link.as_ref()
+                .map_or(0, |n| 1 + depth(&n.left).max(depth(&n.right)))
+        }
+        assert!(depth(&tree.root) <= 520);
+        assert_eq!(keys(&tree), (0..1024).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut tree: SplayTree<i32, i32> = (0..10).map(|k| (k, k)).collect();
+        *tree.get_mut(&4).unwrap() += 100;
+        assert_eq!(tree.get(&4), Some(&104));
+        assert!(tree.get_mut(&40).is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree: SplayTree<i32, i32> = [5, 3, 7, 2, 4, 6, 8]
+            .into_iter()
+            .map(|k| (k, k * 10))
+            .collect();
+        assert_eq!(tree.remove(&5), Some((5, 50)));
+        assert_eq!(tree.remove(&5), None);
+        assert_eq!(tree.remove(&2), Some((2, 20)));
+        assert_eq!(tree.remove(&8), Some((8, 80)));
+        assert_eq!(keys(&tree), vec![3, 4, 6, 7]);
+        assert_eq!(tree.len(), 4);
+        check_node(&tree.root, None, None);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut tree: SplayTree<i32, i32> = (0..20).map(|k| (k, k)).collect();
+        let upper = tree.split(&12);
+        assert_eq!(keys(&tree), (0..12).collect::<Vec<_>>());
+        assert_eq!(keys(&upper), (12..20).collect::<Vec<_>>());
+        assert_eq!(tree.len(), 12);
+        assert_eq!(upper.len(), 8);
+        check_node(&tree.root, None, None);
+        check_node(&upper.root, None, None);
+
+        let mut tree: SplayTree<i32, i32> = (0..20).step_by(2).map(|k| (k, k)).collect();
+        let upper = tree.split(&7);
+        assert_eq!(keys(&tree), vec![0, 2, 4, 6]);
+        assert_eq!(keys(&upper), vec![8, 10, 12, 14, 16, 18]);
+
+        let everything = tree.split(&-1);
+        assert!(tree.is_empty());
+        assert_eq!(everything.len(), 4);
+    }
+
+    #[test]
+    fn test_join() {
+        let mut lower: SplayTree<i32, i32> = (0..10).map(|k| (k, k)).collect();
+        let upper: SplayTree<i32, i32> = (10..25).map(|k| (k, k)).collect();
+        lower.join(upper);
+        assert_eq!(keys(&lower), (0..25).collect::<Vec<_>>());
+        assert_eq!(lower.len(), 25);
+        check_node(&lower.root, None, None);
+
+        let mut empty = SplayTree::new();
+        empty.join(lower);
+        assert_eq!(empty.len(), 25);
+        empty.join(SplayTree::new());
+        assert_eq!(empty.len(), 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "join requires")]
+    fn test_join_overlapping_panics() {
+        let mut lower: SplayTree<i32, i32> = (0..10).map(|k| (k, k)).collect();
+        let upper: SplayTree<i32, i32> = (5..15).map(|k| (k, k)).collect();
+        lower.join(upper);
+    }
+
+    #[test]
+    fn test_split_then_join_roundtrip() {
+        let mut tree: SplayTree<i32, i32> = (0..50).map(|k| ((k * 17) % 50, k)).collect();
+        for pivot in [0, 13, 25, 49, 50] {
+            let upper = tree.split(&pivot);
+            tree.join(upper);
+            assert_eq!(keys(&tree), (0..50).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let tree: SplayTree<i32, &str> = [(2, "b"), (1, "a"), (3, "c")].into_iter().collect();
+        assert_eq!(tree.min(), Some((&1, &"a")));
+        assert_eq!(tree.max(), Some((&3, &"c")));
+        let entries: Vec<(i32, &str)> = tree.into_iter().collect();
+        assert_eq!(entries, vec![(1, "a"), (2, "b"), (3, "c")]);
+    }
+
+    #[test]
+    fn test_drop_long_chains() {
+        const N: u32 = 1_000_000;
+        // Ascending inserts leave a left chain and descending ones a right
+        // chain, so both directions of unlinking are exercised.
+        let ascending: SplayTree<u32, ()> = (0..N).map(|k| (k, ())).collect();
+        assert_eq!(ascending.height(), N as usize);
+        drop(ascending);
+
+        let descending: SplayTree<u32, ()> = (0..N).rev().map(|k| (k, ())).collect();
+        let mut iter = descending.into_iter();
+        assert_eq!(iter.next(), Some((0, ())));
+        drop(iter);
+    }
+
+    #[test]
+    fn test_debug_long_chain() {
+        let tree: SplayTree<u32, u32> = (0..100_000).map(|k| (k, k)).collect();
+        let debug = format!("{tree:?}");
+        assert!(debug.starts_with("{0: 0, 1: 1, "));
+        assert!(debug.ends_with(", 99999: 99999}"));
+    }
+
+    #[test]
+    fn test_randomized_against_reference() {
+        let mut rng = Lcg(0xace);
+        let mut tree = SplayTree::new();
+        let mut reference = std::collections::BTreeMap::new();
+
+        for i in 0..5000 {
+            let key = (rng.next() % 300) as i32;
+            match rng.next() % 3 {
+                0 => assert_eq!(tree.remove(&key), reference.remove_entry(&key)),
+                1 => assert_eq!(tree.get(&key), reference.get(&key)),
+                _ => assert_eq!(tree.insert(key, i), reference.insert(key, i)),
+            }
+        }
+
+        check_node(&tree.root, None, None);
+        assert_eq!(tree.len(), reference.len());
+        assert!(tree.iter().eq(reference.iter()));
+    }
+
+    #[test]
+    fn test_validate_and_statistics() {
+        let mut tree: SplayTree<i32, i32> = (0..100).map(|k| (k, k)).collect();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.height(), 100);
+
+        tree.get(&0);
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.height() < 100);
+        assert_eq!(tree.depth_histogram()[0], 1);
+        assert_eq!(tree.depth_histogram().iter().sum::<usize>(), 100);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: SplayTree<i32, i32> = [(3, 30), (1, 10), (2, 20)].into_iter().collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[[1,10],[2,20],[3,30]]");
+
+        let back: SplayTree<i32, i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(tree.iter()));
+        assert_eq!(back.validate(), Ok(()));
+
+        let keys: Vec<(u32, u32)> = (0..1000).map(|k| (k, k)).collect();
+        let json = serde_json::to_string(&keys).unwrap();
+        let back: SplayTree<u32, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.height(), 10);
+        assert_eq!(back.validate(), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deserialize_rejects_unsorted_keys() {
+        assert!(serde_json::from_str::<SplayTree<i32, i32>>("[[2,0],[1,0]]").is_err());
+        assert!(serde_json::from_str::<SplayTree<i32, i32>>("[[1,0],[1,0]]").is_err());
+    }
+}
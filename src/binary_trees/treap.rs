@@ -0,0 +1,1299 @@
+use std::{cmp::Ordering, fmt, iter::FusedIterator, mem, ops::RangeBounds};
+
+use super::{
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError, Violation},
+};
+use crate::util::index_bounds;
+
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+type Link<K, V> = Option<Box<TreapNode<K, V>>>;
+type SeqLink<T> = Option<Box<SeqNode<T>>>;
+
+/// Xorshift generator for node priorities. Treaps are seeded explicitly so
+/// the shape of a tree is reproducible from its seed and operation sequence.
+#[derive(Debug, Clone)]
+struct XorShift64(u64);
+
+impl XorShift64 {
+    fn new(seed: u64) -> Self {
+        // Xorshift never leaves the all-zero state.
+        Self(if seed == 0 { DEFAULT_SEED } else { seed })
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn fork(&mut self) -> Self {
+        Self::new(self.next())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TreapNode<K, V> {
+    left: Link<K, V>,
+    right: Link<K, V>,
+    key: K,
+    value: V,
+    priority: u64,
+    size: usize,
+}
+
+/// An ordered map kept balanced in expectation by giving every node a random
+/// priority and maintaining heap order on priorities alongside search order
+/// on keys. All updates are expressed through `split` and `merge`.
+#[derive(Debug)]
+pub struct Treap<K, V> {
+    root: Link<K, V>,
+    rng: XorShift64,
+}
+
+#[derive(Debug)]
+pub(crate) struct SeqNode<T> {
+    left: SeqLink<T>,
+    right: SeqLink<T>,
+    value: T,
+    priority: u64,
+    size: usize,
+    reversed: bool,
+}
+
+/// An implicit-key treap: a sequence where a node's position is the size of
+/// everything to its left, so inserting, removing, reversing and
+/// concatenating ranges are all `O(log n)` splits and merges.
+#[derive(Debug)]
+pub struct ImplicitTreap<T> {
+    root: SeqLink<T>,
+    rng: XorShift64,
+}
+
+impl<K, V> TreapNode<K, V> {
+    fn new(key: K, value: V, priority: u64) -> Box<Self> {
+        Box::new(TreapNode {
+            left: None,
+            right: None,
+            key,
+            value,
+            priority,
+            size: 1,
+        })
+    }
+
+    fn parts(&self) -> (&K, Option<&Self>, Option<&Self>) {
+        (&self.key, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn into_entry(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
+    fn size(link: &Link<K, V>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    /// Splits `link` into the nodes whose keys satisfy `goes_left` and the
+    /// rest. `goes_left` must be monotone: true for a prefix of the keys.
+    fn split(link: Link<K, V>, goes_left: &impl Fn(&K) -> bool) -> (Link<K, V>, Link<K, V>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None),
+        };
+
+        if goes_left(&node.key) {
+            let (left, rest) = Self::split(node.right.take(), goes_left);
+            node.right = left;
+            node.update_size();
+            (Some(node), rest)
+        } else {
+            let (rest, right) = Self::split(node.left.take(), goes_left);
+            node.left = right;
+            node.update_size();
+            (rest, Some(node))
+        }
+    }
+
+    /// Merges two treaps where every key in `left` precedes every key in
+    /// `right`.
+    fn merge(left: Link<K, V>, right: Link<K, V>) -> Link<K, V> {
+        match (left, right) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(mut left), Some(mut right)) => {
+                if left.priority > right.priority {
+                    left.right = Self::merge(left.right.take(), Some(right));
+                    left.update_size();
+                    Some(left)
+                } else {
+                    right.left = Self::merge(Some(left), right.left.take());
+                    right.update_size();
+                    Some(right)
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> Default for Treap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> Treap<K, V> {
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            root: None,
+            rng: XorShift64::new(seed),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+
+        let (left, right) = TreapNode::split(self.root.take(), &|k| *k < key);
+        let node = TreapNode::new(key, value, self.rng.next());
+        self.root = TreapNode::merge(TreapNode::merge(left, Some(node)), right);
+        None
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.find(key).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &mut node.left,
+                Ordering::Greater => &mut node.right,
+                Ordering::Equal => return Some(&mut node.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        let (left, rest) = TreapNode::split(self.root.take(), &|k| k < key);
+        let (matched, right) = TreapNode::split(rest, &|k| k <= key);
+        self.root = TreapNode::merge(left, right);
+        matched.map(|node| node.into_entry())
+    }
+
+    /// Moves every entry with a key greater than or equal to `key` into a new
+    /// treap, leaving the smaller keys in `self`.
+    pub fn split_off(&mut self, key: &K) -> Self {
+        let (left, right) = TreapNode::split(self.root.take(), &|k| k < key);
+        self.root = left;
+        Self {
+            root: right,
+            rng: self.rng.fork(),
+        }
+    }
+
+    /// Appends `other` to `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key in `other` is not greater than every key in `self`.
+    pub fn merge(&mut self, mut other: Self) {
+        if let (Some((max, _)), Some((min, _))) = (self.max(), other.min()) {
+            assert!(
+                max < min,
+                "merge requires every key in `other` to be greater than every key in `self`"
+            );
+        }
+        self.root = TreapNode::merge(self.root.take(), other.root.take());
+    }
+
+    /// Checks key order, heap order on priorities and the cached subtree
+    /// sizes.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            false,
+            TreapNode::parts,
+            |n, left, right| {
+                let (_, l, r) = n.parts();
+                if l.into_iter()
+                    .chain(r)
+                    .any(|child| child.priority > n.priority)
+                {
+                    return Err(Violation::HeapOrder);
+                }
+                validate::check_size(n.size, left, right)
+            },
+        )
+        .map(|_| ())
+    }
+
+    fn find(&self, key: &K) -> Option<&TreapNode<K, V>> {
+        let mut link = self.root.as_deref();
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+}
+
+impl<K, V> Treap<K, V> {
+    pub fn len(&self) -> usize {
+        TreapNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, so an empty treap has height zero.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), TreapNode::children)
+    }
+
+    /// Mean number of nodes visited by a successful lookup.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref left) = node.left {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(ref right) = node.right {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        for (k, v) in self.iter() {
+            visit_fn(k, v);
+        }
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&K, &V)) {
+        fn preorder<K, V>(link: &Link<K, V>, visit_fn: &mut impl FnMut(&K, &V)) {
+            if let Some(node) = link {
+                visit_fn(&node.key, &node.value);
+                preorder(&node.left, visit_fn);
+                preorder(&node.right, visit_fn);
+            }
+        }
+        preorder(&self.root, visit_fn);
+    }
+}
+
+impl<K: fmt::Display, V> Treap<K, V> {
+    /// Renders the keys as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &TreapNode<K, V>| n.key.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            TreapNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<K: fmt::Display, V> fmt::Display for Treap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &TreapNode<K, V>| n.key.to_string();
+        render::write_ascii(f, self.root.as_deref(), TreapNode::children, label)
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a TreapNode<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(n) = link {
+            let node: &TreapNode<K, V> = n;
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+pub struct IntoIter<K, V> {
+    stack: Vec<Box<TreapNode<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> IntoIter<K, V> {
+    fn push_left(&mut self, mut link: Link<K, V>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        let right = node.right.take();
+        self.push_left(right);
+        self.remaining -= 1;
+        Some(node.into_entry())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> IntoIterator for Treap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a Treap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Treap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut treap = Self::new();
+        treap.extend(iter);
+        treap
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Treap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<T: fmt::Display> SeqNode<T> {
+    fn label(&self) -> String {
+        if self.reversed {
+            format!("{} [rev]", self.value)
+        } else {
+            self.value.to_string()
+        }
+    }
+}
+
+impl<T> SeqNode<T> {
+    fn new(value: T, priority: u64) -> Box<Self> {
+        Box::new(SeqNode {
+            left: None,
+            right: None,
+            value,
+            priority,
+            size: 1,
+            reversed: false,
+        })
+    }
+
+    // Positions are implicit, so there is no key order to check.
+    fn parts(&self) -> (&(), Option<&Self>, Option<&Self>) {
+        (&(), self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn size(link: &SeqLink<T>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    // Applies a pending reversal to this node's children and hands the flag
+    // down, so the node's own left/right links can be trusted afterwards.
+    fn push_down(&mut self) {
+        if self.reversed {
+            self.reversed = false;
+            mem::swap(&mut self.left, &mut self.right);
+            for child in [&mut self.left, &mut self.right].into_iter().flatten() {
+                child.reversed = !child.reversed;
+            }
+        }
+    }
+
+    /// Splits off the first `count` elements of `link`.
+    fn split(link: SeqLink<T>, count: usize) -> (SeqLink<T>, SeqLink<T>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None),
+        };
+
+        node.push_down();
+        let left_size = Self::size(&node.left);
+        if count <= left_size {
+            let (first, rest) = Self::split(node.left.take(), count);
+            node.left = rest;
+            node.update_size();
+            (first, Some(node))
+        } else {
+            let (first, rest) = Self::split(node.right.take(), count - left_size - 1);
+            node.right = first;
+            node.update_size();
+            (Some(node), rest)
+        }
+    }
+
+    fn merge(left: SeqLink<T>, right: SeqLink<T>) -> SeqLink<T> {
+        match (left, right) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(mut left), Some(mut right)) => {
+                if left.priority > right.priority {
+                    left.push_down();
+                    left.right = Self::merge(left.right.take(), Some(right));
+                    left.update_size();
+                    Some(left)
+                } else {
+                    right.push_down();
+                    right.left = Self::merge(Some(left), right.left.take());
+                    right.update_size();
+                    Some(right)
+                }
+            }
+        }
+    }
+}
+
+impl<T> Default for ImplicitTreap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ImplicitTreap<T> {
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            root: None,
+            rng: XorShift64::new(seed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        SeqNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Height counted in nodes, so an empty sequence has height zero.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), SeqNode::children)
+    }
+
+    /// Mean number of nodes visited by a positional lookup.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    /// Checks heap order on priorities and the cached subtree sizes.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            true,
+            SeqNode::parts,
+            |n, left, right| {
+                let (_, l, r) = n.parts();
+                if l.into_iter()
+                    .chain(r)
+                    .any(|child| child.priority > n.priority)
+                {
+                    return Err(Violation::HeapOrder);
+                }
+                validate::check_size(n.size, left, right)
+            },
+        )
+        .map(|_| ())
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = SeqNode::new(value, self.rng.next());
+        self.root = SeqNode::merge(self.root.take(), Some(node));
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let node = SeqNode::new(value, self.rng.next());
+        self.root = SeqNode::merge(Some(node), self.root.take());
+    }
+
+    /// Inserts `value` so that it ends up at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "insertion index out of bounds");
+        let (left, right) = SeqNode::split(self.root.take(), index);
+        let node = SeqNode::new(value, self.rng.next());
+        self.root = SeqNode::merge(SeqNode::merge(left, Some(node)), right);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let (left, rest) = SeqNode::split(self.root.take(), index);
+        let (node, right) = SeqNode::split(rest, 1);
+        self.root = SeqNode::merge(left, right);
+        node.map(|node| node.value)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.node_at(index).map(|node| &node.value)
+    }
+
+    // Unlike `get`, this pushes pending reversals down along the path.
+    pub fn get_mut(&mut self, mut index: usize) -> Option<&mut T> {
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            node.push_down();
+            let left_size = SeqNode::size(&node.left);
+            link = match index.cmp(&left_size) {
+                Ordering::Less => &mut node.left,
+                Ordering::Equal => return Some(&mut node.value),
+                Ordering::Greater => {
+                    index -= left_size + 1;
+                    &mut node.right
+                }
+            };
+        }
+        None
+    }
+
+    /// Removes the elements in `range` and returns them as a new sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) -> Self {
+        let (start, end) = index_bounds(range, self.len());
+        let (left, rest) = SeqNode::split(self.root.take(), start);
+        let (middle, right) = SeqNode::split(rest, end - start);
+        self.root = SeqNode::merge(left, right);
+        Self {
+            root: middle,
+            rng: self.rng.fork(),
+        }
+    }
+
+    /// Reverses the order of the elements in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is reversed or runs past the end.
+    pub fn reverse_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = index_bounds(range, self.len());
+        let (left, rest) = SeqNode::split(self.root.take(), start);
+        let (mut middle, right) = SeqNode::split(rest, end - start);
+        if let Some(ref mut middle) = middle {
+            middle.reversed = !middle.reversed;
+        }
+        self.root = SeqNode::merge(SeqNode::merge(left, middle), right);
+    }
+
+    /// Moves the elements from position `index` onwards into a new sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn split_off(&mut self, index: usize) -> Self {
+        assert!(index <= self.len(), "split index out of bounds");
+        let (left, right) = SeqNode::split(self.root.take(), index);
+        self.root = left;
+        Self {
+            root: right,
+            rng: self.rng.fork(),
+        }
+    }
+
+    /// Concatenates `other` onto the end of `self`.
+    pub fn append(&mut self, mut other: Self) {
+        self.root = SeqNode::merge(self.root.take(), other.root.take());
+    }
+
+    pub fn iter(&self) -> SeqIter<'_, T> {
+        let mut iter = SeqIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(&self.root, false);
+        iter
+    }
+
+    // Walks down without pushing reversals, tracking instead whether the
+    // pending flags seen so far mirror the current subtree.
+    fn node_at(&self, mut index: usize) -> Option<&SeqNode<T>> {
+        let mut link = &self.root;
+        let mut flipped = false;
+        while let Some(node) = link {
+            flipped ^= node.reversed;
+            let (left, right) = if flipped {
+                (&node.right, &node.left)
+            } else {
+                (&node.left, &node.right)
+            };
+
+            let left_size = SeqNode::size(left);
+            if index < left_size {
+                link = left;
+            } else if index == left_size {
+                return Some(node);
+            } else {
+                index -= left_size + 1;
+                link = right;
+            }
+        }
+        None
+    }
+}
+
+impl<T: fmt::Display> ImplicitTreap<T> {
+    /// Renders the tree as a Graphviz digraph. Nodes are drawn as stored, so
+    /// a subtree with a pending reversal is labelled `[rev]` and its children
+    /// still appear in their unreversed order.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &SeqNode<T>| n.label();
+        render::to_dot(
+            self.root.as_deref(),
+            SeqNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for ImplicitTreap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &SeqNode<T>| n.label();
+        render::write_ascii(f, self.root.as_deref(), SeqNode::children, label)
+    }
+}
+
+pub struct SeqIter<'a, T> {
+    stack: Vec<(&'a SeqNode<T>, bool)>,
+    remaining: usize,
+}
+
+impl<'a, T> SeqIter<'a, T> {
+    fn push_left(&mut self, mut link: &'a SeqLink<T>, mut flipped: bool) {
+        while let Some(n) = link {
+            let node: &SeqNode<T> = n;
+            flipped ^= node.reversed;
+            self.stack.push((node, flipped));
+            link = if flipped { &node.right } else { &node.left };
+        }
+    }
+}
+
+impl<'a, T> Iterator for SeqIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, flipped) = self.stack.pop()?;
+        let right = if flipped { &node.left } else { &node.right };
+        self.push_left(right, flipped);
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for SeqIter<'_, T> {}
+
+impl<T> FusedIterator for SeqIter<'_, T> {}
+
+pub struct SeqIntoIter<T> {
+    stack: Vec<Box<SeqNode<T>>>,
+    remaining: usize,
+}
+
+impl<T> SeqIntoIter<T> {
+    fn push_left(&mut self, mut link: SeqLink<T>) {
+        while let Some(mut node) = link {
+            node.push_down();
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<T> Iterator for SeqIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        let right = node.right.take();
+        self.push_left(right);
+        self.remaining -= 1;
+        Some(node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for SeqIntoIter<T> {}
+
+impl<T> FusedIterator for SeqIntoIter<T> {}
+
+impl<T> IntoIterator for ImplicitTreap<T> {
+    type Item = T;
+    type IntoIter = SeqIntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = SeqIntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ImplicitTreap<T> {
+    type Item = &'a T;
+    type IntoIter = SeqIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> FromIterator<T> for ImplicitTreap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut treap = Self::new();
+        treap.extend(iter);
+        treap
+    }
+}
+
+impl<T> Extend<T> for ImplicitTreap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{ImplicitTreap, Link, Treap, TreapNode, XorShift64};
+
+    // Priorities are not serialized; deserializing draws fresh ones, so only
+    // the contents survive a round trip, not the shape.
+    impl<K: Serialize, V: Serialize> Serialize for Treap<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for Treap<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+            if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+                return Err(de::Error::custom(
+                    "keys are not in strictly ascending order",
+                ));
+            }
+            let mut treap = Self::new();
+            treap.root = build(entries, &mut treap.rng);
+            Ok(treap)
+        }
+    }
+
+    // Builds a treap from entries in key order in `O(n)`. The stack holds the
+    // right spine built so far; each new node adopts the spine nodes with
+    // lower priorities as its left subtree.
+    fn build<K, V>(entries: Vec<(K, V)>, rng: &mut XorShift64) -> Link<K, V> {
+        let mut spine: Vec<Box<TreapNode<K, V>>> = Vec::new();
+        for (key, value) in entries {
+            let mut node = TreapNode::new(key, value, rng.next());
+            let mut left = None;
+            while spine.last().is_some_and(|top| top.priority < node.priority) {
+                let mut top = spine.pop().unwrap();
+                top.right = left;
+                top.update_size();
+                left = Some(top);
+            }
+            node.left = left;
+            spine.push(node);
+        }
+
+        let mut root = None;
+        while let Some(mut node) = spine.pop() {
+            node.right = root;
+            node.update_size();
+            root = Some(node);
+        }
+        root
+    }
+
+    // Serialized in sequence order, with pending reversals applied.
+    impl<T: Serialize> Serialize for ImplicitTreap<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for ImplicitTreap<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns the subtree size after checking key order, heap order on
+    // priorities and cached sizes.
+    fn check_treap(link: &Link<i32, i32>, lo: Option<i32>, hi: Option<i32>) -> usize {
+        match link {
+            Some(n) => {
+                assert!(lo.is_none_or(|lo| n.key > lo));
+                assert!(hi.is_none_or(|hi| n.key < hi));
+                for child in [&n.left, &n.right].into_iter().flatten() {
+                    assert!(child.priority <= n.priority);
+                }
+                let size = 1
+                    + check_treap(&n.left, lo, Some(n.key))
+                    + check_treap(&n.right, Some(n.key), hi);
+                assert_eq!(n.size, size);
+                size
+            }
+            None => 0,
+        }
+    }
+
+    fn check_seq(link: &SeqLink<i32>) -> usize {
+        match link {
+            Some(n) => {
+                for child in [&n.left, &n.right].into_iter().flatten() {
+                    assert!(child.priority <= n.priority);
+                }
+                let size = 1 + check_seq(&n.left) + check_seq(&n.right);
+                assert_eq!(n.size, size);
+                size
+            }
+            None => 0,
+        }
+    }
+
+    fn keys(treap: &Treap<i32, i32>) -> Vec<i32> {
+        treap.iter().map(|(k, _)| *k).collect()
+    }
+
+    fn values(seq: &ImplicitTreap<i32>) -> Vec<i32> {
+        seq.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut treap = Treap::new();
+        assert_eq!(treap.insert(5, 50), None);
+        assert_eq!(treap.insert(3, 30), None);
+        assert_eq!(treap.insert(5, 55), Some(50));
+
+        assert_eq!(treap.len(), 2);
+        assert_eq!(treap.get(&5), Some(&55));
+        assert_eq!(treap.get(&3), Some(&30));
+        assert_eq!(treap.get(&4), None);
+        assert!(treap.contains_key(&3));
+        check_treap(&treap.root, None, None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut treap: Treap<i32, i32> = (0..50).map(|k| (k, k * 2)).collect();
+        assert_eq!(treap.remove(&10), Some((10, 20)));
+        assert_eq!(treap.remove(&10), None);
+        assert_eq!(treap.len(), 49);
+        assert!(!treap.contains_key(&10));
+        check_treap(&treap.root, None, None);
+    }
+
+    #[test]
+    fn test_sorted_insert_is_balanced_in_expectation() {
+        let treap: Treap<i32, i32> = (0..4096).map(|k| (k, k)).collect();
+        fn depth(link: &Link<i32, i32>) -> usize {
+            link.as_ref()
+                .map_or(0, |n| 1 + depth(&n.left).max(depth(&n.right)))
+        }
+        assert!(depth(&treap.root) < 60);
+        assert_eq!(keys(&treap), (0..4096).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_same_seed_same_shape() {
+        let build = |seed| {
+            let mut treap = Treap::with_seed(seed);
+            for k in [8, 3, 10, 1, 6, 14, 4, 7, 13] {
+                treap.insert(k, ());
+            }
+            let mut shape = vec![];
+            treap.preorder_traversal(&mut |k, _| shape.push(*k));
+            shape
+        };
+        assert_eq!(build(42), build(42));
+        assert_eq!(build(7), build(7));
+    }
+
+    #[test]
+    fn test_split_off_and_merge() {
+        let mut treap: Treap<i32, i32> = (0..30).map(|k| (k, k)).collect();
+        let upper = treap.split_off(&18);
+        assert_eq!(keys(&treap), (0..18).collect::<Vec<_>>());
+        assert_eq!(keys(&upper), (18..30).collect::<Vec<_>>());
+        check_treap(&treap.root, None, None);
+        check_treap(&upper.root, None, None);
+
+        treap.merge(upper);
+        assert_eq!(keys(&treap), (0..30).collect::<Vec<_>>());
+        check_treap(&treap.root, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "merge requires")]
+    fn test_merge_overlapping_panics() {
+        let mut lower: Treap<i32, i32> = (0..10).map(|k| (k, k)).collect();
+        let upper: Treap<i32, i32> = (5..15).map(|k| (k, k)).collect();
+        lower.merge(upper);
+    }
+
+    #[test]
+    fn test_implicit_insert_and_remove() {
+        let mut seq = ImplicitTreap::new();
+        seq.push_back(1);
+        seq.push_back(3);
+        seq.push_front(0);
+        seq.insert(2, 2);
+        seq.insert(4, 4);
+        assert_eq!(values(&seq), vec![0, 1, 2, 3, 4]);
+        assert_eq!(seq.get(2), Some(&2));
+        assert_eq!(seq.get(5), None);
+
+        assert_eq!(seq.remove(1), Some(1));
+        assert_eq!(seq.remove(10), None);
+        assert_eq!(values(&seq), vec![0, 2, 3, 4]);
+        *seq.get_mut(0).unwrap() = 9;
+        assert_eq!(seq.get(0), Some(&9));
+        check_seq(&seq.root);
+    }
+
+    #[test]
+    fn test_implicit_reverse_range() {
+        let mut seq: ImplicitTreap<i32> = (0..10).collect();
+        seq.reverse_range(2..7);
+        assert_eq!(values(&seq), vec![0, 1, 6, 5, 4, 3, 2, 7, 8, 9]);
+        assert_eq!(seq.get(2), Some(&6));
+        assert_eq!(seq.get(6), Some(&2));
+
+        seq.reverse_range(..);
+        assert_eq!(values(&seq), vec![9, 8, 7, 2, 3, 4, 5, 6, 1, 0]);
+        seq.reverse_range(3..=3);
+        assert_eq!(seq.get(3), Some(&2));
+        check_seq(&seq.root);
+    }
+
+    #[test]
+    fn test_implicit_remove_range_and_append() {
+        let mut seq: ImplicitTreap<i32> = (0..10).collect();
+        let middle = seq.remove_range(3..6);
+        assert_eq!(values(&seq), vec![0, 1, 2, 6, 7, 8, 9]);
+        assert_eq!(values(&middle), vec![3, 4, 5]);
+
+        let tail = seq.split_off(5);
+        assert_eq!(values(&tail), vec![8, 9]);
+        seq.append(middle);
+        seq.append(tail);
+        assert_eq!(values(&seq), vec![0, 1, 2, 6, 7, 3, 4, 5, 8, 9]);
+        check_seq(&seq.root);
+    }
+
+    #[test]
+    fn test_implicit_into_iter_applies_reversals() {
+        let mut seq: ImplicitTreap<i32> = (0..8).collect();
+        seq.reverse_range(1..5);
+        seq.reverse_range(0..3);
+        let owned: Vec<i32> = seq.into_iter().collect();
+        assert_eq!(owned, vec![3, 4, 0, 2, 1, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_implicit_get_mut_under_reversal() {
+        let mut seq: ImplicitTreap<i32> = (0..100).collect();
+        seq.reverse_range(10..90);
+        for i in (0..100).step_by(7) {
+            *seq.get_mut(i).unwrap() += 1000;
+        }
+        let expected: Vec<i32> = (0..10)
+            .chain((10..90).rev())
+            .chain(90..100)
+            .enumerate()
+            .map(|(i, v)| if i % 7 == 0 { v + 1000 } else { v })
+            .collect();
+        assert_eq!(values(&seq), expected);
+        assert!(seq.get_mut(100).is_none());
+        check_seq(&seq.root);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let treap: Treap<i32, String> = (0..100).rev().map(|k| (k, k.to_string())).collect();
+        let mut iter = treap.into_iter();
+        assert_eq!(iter.len(), 100);
+        assert_eq!(iter.next(), Some((0, "0".to_string())));
+        assert_eq!(iter.len(), 99);
+        assert!(iter
+            .map(|(k, v)| (k, v.parse::<i32>().unwrap()))
+            .eq((1..100).map(|k| (k, k))));
+    }
+
+    #[test]
+    fn test_implicit_randomized_against_vec() {
+        let mut rng = XorShift64::new(0xfeed);
+        let mut seq = ImplicitTreap::with_seed(1);
+        let mut reference: Vec<i32> = Vec::new();
+
+        for i in 0..3000 {
+            let len = reference.len();
+            match rng.next() % 5 {
+                0 | 1 => {
+                    let index = rng.next() as usize % (len + 1);
+                    seq.insert(index, i);
+                    reference.insert(index, i);
+                }
+                2 if len > 0 => {
+                    let index = rng.next() as usize % len;
+                    assert_eq!(seq.remove(index), Some(reference.remove(index)));
+                }
+                3 => {
+                    let a = rng.next() as usize % (len + 1);
+                    let b = rng.next() as usize % (len + 1);
+                    let (start, end) = (a.min(b), a.max(b));
+                    seq.reverse_range(start..end);
+                    reference[start..end].reverse();
+                }
+                _ => {
+                    let a = rng.next() as usize % (len + 1);
+                    let b = rng.next() as usize % (len + 1);
+                    let (start, end) = (a.min(b), a.max(b));
+                    let removed = seq.remove_range(start..end);
+                    let expected: Vec<i32> = reference.drain(start..end).collect();
+                    assert_eq!(values(&removed), expected);
+                }
+            }
+        }
+
+        check_seq(&seq.root);
+        assert_eq!(values(&seq), reference);
+        for (i, val) in reference.iter().enumerate() {
+            assert_eq!(seq.get(i), Some(val));
+        }
+    }
+
+    #[test]
+    fn test_validate_and_statistics() {
+        let mut treap: Treap<i32, i32> = (0..1000).map(|k| (k, k)).collect();
+        assert_eq!(treap.validate(), Ok(()));
+        assert_eq!(treap.depth_histogram().len(), treap.height());
+        assert!(treap.average_search_depth() < 30.0);
+
+        let root = treap.root.as_mut().unwrap();
+        root.left.as_mut().unwrap().priority = u64::MAX;
+        let err = treap.validate().unwrap_err();
+        assert_eq!(err.path, "");
+        assert_eq!(err.violation, Violation::HeapOrder);
+
+        let mut seq: ImplicitTreap<i32> = (0..500).collect();
+        seq.reverse_range(100..400);
+        assert_eq!(seq.validate(), Ok(()));
+        assert_eq!(seq.depth_histogram().iter().sum::<usize>(), 500);
+    }
+
+    #[test]
+    fn test_render() {
+        let treap: Treap<i32, ()> = (0..20).map(|k| (k, ())).collect();
+        let dot = treap.to_dot_with(DotOptions::all());
+        let edges = dot
+            .lines()
+            .filter(|l| l.contains(" -> n") && !l.contains("nil"));
+        assert_eq!(edges.count(), 19);
+        assert_eq!(
+            treap
+                .to_string()
+                .lines()
+                .filter(|l| !l.ends_with("nil"))
+                .count(),
+            20
+        );
+
+        let mut seq: ImplicitTreap<i32> = (0..5).collect();
+        assert!(!seq.to_string().contains("[rev]"));
+        seq.reverse_range(..);
+        assert!(seq.to_string().lines().next().unwrap().ends_with(" [rev]"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let treap: Treap<i32, char> = [(2, 'b'), (1, 'a'), (3, 'c')].into_iter().collect();
+        let json = serde_json::to_string(&treap).unwrap();
+        assert_eq!(json, r#"[[1,"a"],[2,"b"],[3,"c"]]"#);
+
+        let back: Treap<i32, char> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(treap.iter()));
+        assert_eq!(back.validate(), Ok(()));
+
+        let keys: Vec<(u32, u32)> = (0..4096).map(|k| (k, k)).collect();
+        let json = serde_json::to_string(&keys).unwrap();
+        let back: Treap<u32, u32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().map(|(k, _)| *k).eq(0..4096));
+        assert!(back.height() < 60);
+        assert_eq!(back.validate(), Ok(()));
+
+        let mut seq: ImplicitTreap<i32> = (0..6).collect();
+        seq.reverse_range(1..5);
+        let json = serde_json::to_string(&seq).unwrap();
+        assert_eq!(json, "[0,4,3,2,1,5]");
+
+        let back: ImplicitTreap<i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(seq.iter()));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deserialize_rejects_unsorted_keys() {
+        assert!(serde_json::from_str::<Treap<i32, i32>>("[[2,0],[1,0]]").is_err());
+        assert!(serde_json::from_str::<Treap<i32, i32>>("[[1,0],[1,0]]").is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Structural checks and shape statistics shared by the trees in this module.
+
+use std::fmt;
+
+/// A broken tree invariant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// An element does not fit between the elements of its ancestors.
+    OutOfOrder,
+    StaleSize {
+        cached: usize,
+        actual: usize,
+    },
+    StaleHeight {
+        cached: usize,
+        actual: usize,
+    },
+    Unbalanced {
+        left: usize,
+        right: usize,
+    },
+    /// One subtree weighs more than three times the other, counting a
+    /// subtree's weight as its size plus one.
+    Overweight {
+        left: usize,
+        right: usize,
+    },
+    /// A cached maximum end point differs from the largest end in its
+    /// subtree.
+    StaleMaxEnd,
+    /// A child has a higher priority than its parent.
+    HeapOrder,
+    RedRoot,
+    RedRedEdge,
+    RightLeaningRed,
+    BlackHeightMismatch {
+        left: usize,
+        right: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::OutOfOrder => write!(f, "keys are out of order"),
+            Violation::StaleSize { cached, actual } => {
+                write!(
+                    f,
+                    "cached subtree size is {cached} but the subtree has {actual} nodes"
+                )
+            }
+            Violation::StaleHeight { cached, actual } => {
+                write!(
+                    f,
+                    "cached height is {cached} but the subtree is {actual} tall"
+                )
+            }
+            Violation::Unbalanced { left, right } => {
+                write!(
+                    f,
+                    "subtree heights differ by more than one: left {left}, right {right}"
+                )
+            }
+            Violation::Overweight { left, right } => {
+                write!(
+                    f,
+                    "subtree weights out of balance: left {left}, right {right}"
+                )
+            }
+            Violation::StaleMaxEnd => {
+                write!(f, "cached maximum end point does not match the subtree")
+            }
+            Violation::HeapOrder => write!(f, "child priority exceeds its parent's"),
+            Violation::RedRoot => write!(f, "root is red"),
+            Violation::RedRedEdge => write!(f, "red node has a red child"),
+            Violation::RightLeaningRed => write!(f, "red link leans right"),
+            Violation::BlackHeightMismatch { left, right } => {
+                write!(f, "black height mismatch: left {left}, right {right}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// The first violation found by a tree's `validate`, together with where it
+/// was found: `path` spells out the left (`L`) and right (`R`) turns from the
+/// root, so the root itself has an empty path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantError {
+    pub path: String,
+    pub violation: Violation,
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{} at the root", self.violation)
+        } else {
+            write!(f, "{} at node {}", self.violation, self.path)
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+struct Frame<'a, N, T> {
+    node: &'a N,
+    lo: Option<&'a T>,
+    hi: Option<&'a T>,
+    side: Option<char>,
+    expanded: bool,
+}
+
+/// Walks a tree without recursing. On the way down every key is checked
+/// against the bounds set by its ancestors (ties are accepted only when
+/// `allow_equal` is set); on the way up `summarize` receives each node with
+/// the summaries of its children and either folds them or reports a
+/// violation. Returns the root's summary.
+pub(crate) fn walk<'a, N, T, A>(
+    root: Option<&'a N>,
+    allow_equal: bool,
+    parts: impl Fn(&'a N) -> (&'a T, Option<&'a N>, Option<&'a N>),
+    mut summarize: impl FnMut(&'a N, Option<A>, Option<A>) -> Result<A, Violation>,
+) -> Result<Option<A>, InvariantError>
+where
+    T: PartialOrd + 'a,
+{
+    // Only expanded frames are ancestors of the node being looked at; the
+    // others are right siblings waiting their turn.
+    fn path<N, T>(stack: &[Frame<'_, N, T>]) -> String {
+        stack
+            .iter()
+            .filter(|frame| frame.expanded)
+            .filter_map(|frame| frame.side)
+            .collect()
+    }
+
+    let fits = |lower: &T, upper: &T| {
+        if allow_equal {
+            lower <= upper
+        } else {
+            lower < upper
+        }
+    };
+
+    let mut stack: Vec<Frame<'a, N, T>> = Vec::new();
+    let mut summaries = Vec::new();
+    if let Some(root) = root {
+        stack.push(Frame {
+            node: root,
+            lo: None,
+            hi: None,
+            side: None,
+            expanded: false,
+        });
+    }
+
+    while let Some(top) = stack.last_mut() {
+        let (key, left, right) = parts(top.node);
+        if !top.expanded {
+            top.expanded = true;
+            let (lo, hi) = (top.lo, top.hi);
+            if !lo.is_none_or(|lo| fits(lo, key)) || !hi.is_none_or(|hi| fits(key, hi)) {
+                return Err(InvariantError {
+                    path: path(&stack),
+                    violation: Violation::OutOfOrder,
+                });
+            }
+
+            if let Some(right) = right {
+                stack.push(Frame {
+                    node: right,
+                    lo: Some(key),
+                    hi,
+                    side: Some('R'),
+                    expanded: false,
+                });
+            }
+            if let Some(left) = left {
+                stack.push(Frame {
+                    node: left,
+                    lo,
+                    hi: Some(key),
+                    side: Some('L'),
+                    expanded: false,
+                });
+            }
+        } else {
+            let node = top.node;
+            let right = right.and_then(|_| summaries.pop());
+            let left = left.and_then(|_| summaries.pop());
+            match summarize(node, left, right) {
+                Ok(summary) => {
+                    stack.pop();
+                    summaries.push(summary);
+                }
+                Err(violation) => {
+                    return Err(InvariantError {
+                        path: path(&stack),
+                        violation,
+                    })
+                }
+            }
+        }
+    }
+
+    Ok(summaries.pop())
+}
+
+/// Folds the sizes of a node's subtrees, checking them against its cached
+/// size.
+pub(crate) fn check_size(
+    cached: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+) -> Result<usize, Violation> {
+    let actual = 1 + left.unwrap_or(0) + right.unwrap_or(0);
+    if cached == actual {
+        Ok(actual)
+    } else {
+        Err(Violation::StaleSize { cached, actual })
+    }
+}
+
+/// Counts the nodes at each depth, with the root at depth zero. The length
+/// of the result is the height of the tree.
+pub(crate) fn depth_histogram<'a, N>(
+    root: Option<&'a N>,
+    children: impl Fn(&'a N) -> (Option<&'a N>, Option<&'a N>),
+) -> Vec<usize> {
+    let mut histogram = Vec::new();
+    let mut stack: Vec<(&N, usize)> = root.map(|root| (root, 0)).into_iter().collect();
+    while let Some((node, depth)) = stack.pop() {
+        if histogram.len() <= depth {
+            histogram.push(0);
+        }
+        histogram[depth] += 1;
+
+        let (left, right) = children(node);
+        stack.extend(left.map(|n| (n, depth + 1)));
+        stack.extend(right.map(|n| (n, depth + 1)));
+    }
+    histogram
+}
+
+/// Mean number of nodes a successful search visits, counting the root as
+/// one. Zero for an empty tree.
+pub(crate) fn average_search_depth(histogram: &[usize]) -> f64 {
+    let nodes: usize = histogram.iter().sum();
+    if nodes == 0 {
+        return 0.0;
+    }
+
+    let visited: usize = histogram
+        .iter()
+        .enumerate()
+        .map(|(depth, count)| (depth + 1) * count)
+        .sum();
+    visited as f64 / nodes as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A minimal tree for exercising the walker directly.
+    struct Node {
+        key: i32,
+        left: Option<Box<Node>>,
+        right: Option<Box<Node>>,
+    }
+
+    fn node(key: i32, left: Option<Box<Node>>, right: Option<Box<Node>>) -> Option<Box<Node>> {
+        Some(Box::new(Node { key, left, right }))
+    }
+
+    fn sizes(root: &Option<Box<Node>>, allow_equal: bool) -> Result<Option<usize>, InvariantError> {
+        walk(
+            root.as_deref(),
+            allow_equal,
+            |n| (&n.key, n.left.as_deref(), n.right.as_deref()),
+            |_, l, r| Ok(1 + l.unwrap_or(0) + r.unwrap_or(0)),
+        )
+    }
+
+    #[test]
+    fn test_walk_summarizes_bottom_up() {
+        let tree = node(
+            4,
+            node(2, node(1, None, None), node(3, None, None)),
+            node(6, None, None),
+        );
+        assert_eq!(sizes(&tree, false), Ok(Some(5)));
+        assert_eq!(sizes(&None, false), Ok(None));
+    }
+
+    #[test]
+    fn test_walk_reports_path_of_misplaced_key() {
+        // 5 sits in the left subtree of 4.
+        let tree = node(
+            4,
+            node(2, node(1, None, None), node(5, None, None)),
+            node(6, None, None),
+        );
+        let err = sizes(&tree, false).unwrap_err();
+        assert_eq!(err.path, "LR");
+        assert_eq!(err.violation, Violation::OutOfOrder);
+        assert_eq!(err.to_string(), "keys are out of order at node LR");
+    }
+
+    #[test]
+    fn test_walk_ties() {
+        let tree = node(2, node(2, None, None), node(2, None, None));
+        assert_eq!(sizes(&tree, true), Ok(Some(3)));
+        assert_eq!(sizes(&tree, false).unwrap_err().path, "L");
+    }
+
+    #[test]
+    fn test_walk_reports_summarize_errors_at_node() {
+        let tree = node(2, node(1, None, None), node(3, None, None));
+        let err = walk(
+            tree.as_deref(),
+            false,
+            |n| (&n.key, n.left.as_deref(), n.right.as_deref()),
+            |n, _: Option<()>, _| {
+                if n.key == 3 {
+                    Err(Violation::HeapOrder)
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.path, "R");
+        assert_eq!(
+            err.to_string(),
+            "child priority exceeds its parent's at node R"
+        );
+    }
+
+    #[test]
+    fn test_depth_histogram_and_average() {
+        let tree = node(4, node(2, node(1, None, None), None), node(6, None, None));
+        let histogram =
+            depth_histogram(tree.as_deref(), |n| (n.left.as_deref(), n.right.as_deref()));
+        assert_eq!(histogram, vec![1, 2, 1]);
+        assert_eq!(average_search_depth(&histogram), 2.0);
+        assert_eq!(average_search_depth(&[]), 0.0);
+    }
+}
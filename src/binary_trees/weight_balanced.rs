@@ -0,0 +1,824 @@
+use std::{cmp::Ordering, fmt, iter::FusedIterator, ops::RangeBounds, ptr};
+
+use super::{
+    bst::{above_start, below_end, count_in_range, partial_order},
+    render::{self, DotOptions},
+    validate::{self, walk, InvariantError, Violation},
+};
+
+// Adams' balance parameters, in the (3, 2) variant shown correct by Hirai
+// and Yamamoto. A subtree's weight is its size plus one.
+const DELTA: usize = 3;
+const GAMMA: usize = 2;
+
+type Link<T> = Option<Box<WBNode<T>>>;
+
+pub(crate) struct WBNode<T> {
+    left: Link<T>,
+    right: Link<T>,
+    data: T,
+    size: usize,
+}
+
+/// A weight-balanced tree (a BB\[alpha\] tree): neither subtree of a node may
+/// weigh more than three times the other. The cached subtree sizes double
+/// as balance information, so `rank` and `select` come for free.
+pub struct WeightBalancedTree<T> {
+    root: Link<T>,
+}
+
+impl<T> WBNode<T> {
+    fn size(link: &Link<T>) -> usize {
+        link.as_ref().map_or(0, |n| n.size)
+    }
+
+    fn weight(link: &Link<T>) -> usize {
+        Self::size(link) + 1
+    }
+
+    fn update(&mut self) {
+        self.size = 1 + Self::size(&self.left) + Self::size(&self.right);
+    }
+
+    fn parts(&self) -> (&T, Option<&Self>, Option<&Self>) {
+        (&self.data, self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn children(&self) -> (Option<&Self>, Option<&Self>) {
+        (self.left.as_deref(), self.right.as_deref())
+    }
+
+    fn rotate_left(mut node: Box<Self>) -> Box<Self> {
+        let mut right = node.right.take().unwrap();
+        node.right = right.left.take();
+        node.update();
+        right.left = Some(node);
+        right.update();
+        right
+    }
+
+    fn rotate_right(mut node: Box<Self>) -> Box<Self> {
+        let mut left = node.left.take().unwrap();
+        node.left = left.right.take();
+        node.update();
+        left.right = Some(node);
+        left.update();
+        left
+    }
+
+    /// Restores the balance of a node whose children are balanced and
+    /// differ from a balanced state by at most one insertion or deletion.
+    fn balance(mut node: Box<Self>) -> Box<Self> {
+        let (left, right) = (Self::weight(&node.left), Self::weight(&node.right));
+        if right > DELTA * left {
+            let inner = node.right.as_mut().unwrap();
+            if Self::weight(&inner.left) >= GAMMA * Self::weight(&inner.right) {
+                node.right = node.right.take().map(Self::rotate_right);
+            }
+            Self::rotate_left(node)
+        } else if left > DELTA * right {
+            let inner = node.left.as_mut().unwrap();
+            if Self::weight(&inner.right) >= GAMMA * Self::weight(&inner.left) {
+                node.left = node.left.take().map(Self::rotate_left);
+            }
+            Self::rotate_right(node)
+        } else {
+            node.update();
+            node
+        }
+    }
+
+    fn take_min(mut node: Box<Self>) -> (Box<Self>, Link<T>) {
+        match node.left.take() {
+            Some(left) => {
+                let (min, rest) = Self::take_min(left);
+                node.left = rest;
+                (min, Some(Self::balance(node)))
+            }
+            None => {
+                let rest = node.right.take();
+                (node, rest)
+            }
+        }
+    }
+
+    fn take_max(mut node: Box<Self>) -> (Box<Self>, Link<T>) {
+        match node.right.take() {
+            Some(right) => {
+                let (max, rest) = Self::take_max(right);
+                node.right = rest;
+                (max, Some(Self::balance(node)))
+            }
+            None => {
+                let rest = node.left.take();
+                (node, rest)
+            }
+        }
+    }
+}
+
+impl<T: PartialOrd> WBNode<T> {
+    fn insert(link: Link<T>, data: T) -> Box<Self> {
+        let Some(mut node) = link else {
+            return Box::new(WBNode {
+                left: None,
+                right: None,
+                data,
+                size: 1,
+            });
+        };
+
+        if data < node.data {
+            node.left = Some(Self::insert(node.left.take(), data));
+        } else {
+            node.right = Some(Self::insert(node.right.take(), data));
+        }
+        Self::balance(node)
+    }
+
+    fn remove(link: &mut Link<T>, val: &T) -> bool {
+        let Some(mut node) = link.take() else {
+            return false;
+        };
+
+        let removed = match partial_order(val, &node.data) {
+            Ordering::Less => Self::remove(&mut node.left, val),
+            Ordering::Greater => Self::remove(&mut node.right, val),
+            Ordering::Equal => {
+                *link = match (node.left.take(), node.right.take()) {
+                    (None, right) => right,
+                    (left, None) => left,
+                    (left, Some(right)) => {
+                        let (mut successor, rest) = Self::take_min(right);
+                        successor.left = left;
+                        successor.right = rest;
+                        Some(Self::balance(successor))
+                    }
+                };
+                return true;
+            }
+        };
+        *link = Some(Self::balance(node));
+        removed
+    }
+}
+
+impl<T: PartialOrd> Default for WeightBalancedTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PartialOrd> WeightBalancedTree<T> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn insert(&mut self, val: T) {
+        self.root = Some(WBNode::insert(self.root.take(), val));
+    }
+
+    pub fn search(&self, val: T) -> bool {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match partial_order(&val, &node.data) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    pub fn delete(&mut self, val: T) {
+        WBNode::remove(&mut self.root, &val);
+    }
+
+    /// Largest element less than or equal to `val`.
+    pub fn floor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data <= *val {
+                best = Some(&node.data);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        best
+    }
+
+    /// Smallest element greater than or equal to `val`.
+    pub fn ceiling(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data >= *val {
+                best = Some(&node.data);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        best
+    }
+
+    /// Largest element strictly less than `val`.
+    pub fn predecessor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data < *val {
+                best = Some(&node.data);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        best
+    }
+
+    /// Smallest element strictly greater than `val`.
+    pub fn successor(&self, val: &T) -> Option<&T> {
+        let mut link = &self.root;
+        let mut best = None;
+        while let Some(node) = link {
+            if node.data > *val {
+                best = Some(&node.data);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        best
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let (min, rest) = WBNode::take_min(self.root.take()?);
+        self.root = rest;
+        Some(min.data)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        let (max, rest) = WBNode::take_max(self.root.take()?);
+        self.root = rest;
+        Some(max.data)
+    }
+
+    /// Number of elements strictly less than `val`.
+    pub fn rank(&self, val: &T) -> usize {
+        self.count_less(val, false)
+    }
+
+    pub fn count_range<R: RangeBounds<T>>(&self, range: R) -> usize {
+        count_in_range(&range, self.len(), |val, inclusive| {
+            self.count_less(val, inclusive)
+        })
+    }
+
+    fn count_less(&self, val: &T, inclusive: bool) -> usize {
+        let mut link = &self.root;
+        let mut count = 0;
+        while let Some(node) = link {
+            if node.data < *val || (inclusive && node.data <= *val) {
+                count += WBNode::size(&node.left) + 1;
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        count
+    }
+
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T> {
+        let mut stack = Vec::new();
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &WBNode<T> = n;
+            if above_start(&node.data, range.start_bound()) {
+                stack.push(node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+
+        let mut last = None;
+        let mut link = &self.root;
+        while let Some(n) = link {
+            let node: &WBNode<T> = n;
+            if below_end(&node.data, range.end_bound()) {
+                last = Some(node);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+
+        match (stack.last(), last) {
+            (Some(first), Some(last)) if first.data <= last.data => Range {
+                stack,
+                last: Some(last),
+            },
+            _ => Range {
+                stack: Vec::new(),
+                last: None,
+            },
+        }
+    }
+
+    /// Checks ordering, the cached subtree sizes and the weight balance,
+    /// reporting the first node that breaks any of them.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        walk(
+            self.root.as_deref(),
+            true,
+            WBNode::parts,
+            |n, left, right| {
+                let size = validate::check_size(n.size, left, right)?;
+                let (left, right) = (left.unwrap_or(0) + 1, right.unwrap_or(0) + 1);
+                if left > DELTA * right || right > DELTA * left {
+                    return Err(Violation::Overweight { left, right });
+                }
+                Ok(size)
+            },
+        )
+        .map(|_| ())
+    }
+}
+
+impl<T> WeightBalancedTree<T> {
+    pub fn len(&self) -> usize {
+        WBNode::size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Number of nodes on the longest root-to-leaf path.
+    pub fn height(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    /// Number of nodes at each depth, starting with the root.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        validate::depth_histogram(self.root.as_deref(), WBNode::children)
+    }
+
+    /// Mean number of nodes visited by a successful search.
+    pub fn average_search_depth(&self) -> f64 {
+        validate::average_search_depth(&self.depth_histogram())
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(&node.data)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(&node.data)
+    }
+
+    /// The `k`-th smallest element, counting from zero.
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let left_size = WBNode::size(&node.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => link = &node.left,
+                Ordering::Equal => return Some(&node.data),
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    link = &node.right;
+                }
+            }
+        }
+        None
+    }
+
+    pub fn inorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        self.iter().for_each(visit_fn);
+    }
+
+    pub fn preorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        let mut stack: Vec<&WBNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            visit_fn(&node.data);
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+        }
+    }
+
+    pub fn postorder_traversal(&self, visit_fn: &mut impl FnMut(&T)) {
+        let mut stack: Vec<(&WBNode<T>, bool)> = self
+            .root
+            .as_deref()
+            .map(|n| (n, false))
+            .into_iter()
+            .collect();
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                visit_fn(&node.data);
+                continue;
+            }
+            stack.push((node, true));
+            stack.extend(node.right.as_deref().map(|n| (n, false)));
+            stack.extend(node.left.as_deref().map(|n| (n, false)));
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<T: fmt::Display> WeightBalancedTree<T> {
+    /// Renders the tree as a Graphviz digraph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: DotOptions) -> String {
+        let label = |n: &WBNode<T>| n.data.to_string();
+        render::to_dot(
+            self.root.as_deref(),
+            WBNode::children,
+            label,
+            |_| None,
+            options,
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for WeightBalancedTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |n: &WBNode<T>| n.data.to_string();
+        render::write_ascii(f, self.root.as_deref(), WBNode::children, label)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for WeightBalancedTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    stack: Vec<&'a WBNode<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut link: &'a Link<T>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+pub struct Range<'a, T> {
+    stack: Vec<&'a WBNode<T>>,
+    // The largest node inside the range.
+    last: Option<&'a WBNode<T>>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if self.last.is_some_and(|last| ptr::eq(last, node)) {
+            self.stack.clear();
+        } else {
+            let mut link = &node.right;
+            while let Some(next) = link {
+                self.stack.push(next);
+                link = &next.left;
+            }
+        }
+        Some(&node.data)
+    }
+}
+
+impl<T> FusedIterator for Range<'_, T> {}
+
+pub struct IntoIter<T> {
+    stack: Vec<Box<WBNode<T>>>,
+    remaining: usize,
+}
+
+impl<T> IntoIter<T> {
+    fn push_left(&mut self, mut link: Link<T>) {
+        while let Some(mut node) = link {
+            link = node.left.take();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        self.push_left(node.right.take());
+        self.remaining -= 1;
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for WeightBalancedTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, T> IntoIterator for &'a WeightBalancedTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for WeightBalancedTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T: PartialOrd> Extend<T> for WeightBalancedTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for val in iter {
+            self.insert(val);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::WeightBalancedTree;
+
+    // Serialized as a sequence in ascending order.
+    impl<T: Serialize> Serialize for WeightBalancedTree<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T: Deserialize<'de> + PartialOrd> Deserialize<'de> for WeightBalancedTree<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Vec::<T>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Lcg;
+    use std::{
+        collections::{BTreeMap, VecDeque},
+        ops::Bound,
+    };
+
+    // A weight-balanced tree with n nodes is at most log_{4/3}(n + 1) tall.
+    fn height_bound(len: usize) -> usize {
+        ((len + 1) as f64).ln().div_euclid((4.0f64 / 3.0).ln()) as usize + 1
+    }
+
+    #[test]
+    fn test_basic_operations() {
+        let mut tree: WeightBalancedTree<i32> = [5, 3, 8, 1, 4, 7, 9].into_iter().collect();
+        assert_eq!(tree.len(), 7);
+        assert!(tree.search(4));
+        assert!(!tree.search(6));
+        assert_eq!(tree.min(), Some(&1));
+        assert_eq!(tree.max(), Some(&9));
+        assert_eq!(tree.floor(&6), Some(&5));
+        assert_eq!(tree.ceiling(&6), Some(&7));
+        assert_eq!(tree.rank(&7), 4);
+        assert_eq!(tree.select(4), Some(&7));
+        assert_eq!(tree.select(7), None);
+
+        tree.delete(5);
+        tree.delete(42);
+        assert_eq!(tree.len(), 6);
+        assert!(tree.iter().copied().eq([1, 3, 4, 7, 8, 9]));
+        assert_eq!(format!("{tree:?}"), "{1, 3, 4, 7, 8, 9}");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_sorted_inserts_stay_balanced() {
+        let mut tree = WeightBalancedTree::new();
+        for k in 0..5000u64 {
+            tree.insert(k);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.height() <= height_bound(tree.len()));
+
+        for k in (0..5000).step_by(2) {
+            tree.delete(k);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.iter().copied().eq((1..5000).step_by(2)));
+    }
+
+    #[test]
+    fn test_random_against_btreemap() {
+        let mut rng = Lcg(23);
+        let mut tree = WeightBalancedTree::new();
+        let mut reference: BTreeMap<u64, usize> = BTreeMap::new();
+
+        for i in 0..20_000 {
+            let key = rng.next() % 1000;
+            if rng.next() % 5 < 2 {
+                tree.delete(key);
+                if let Some(count) = reference.get_mut(&key) {
+                    *count -= 1;
+                    if *count == 0 {
+                        reference.remove(&key);
+                    }
+                }
+            } else {
+                tree.insert(key);
+                *reference.entry(key).or_default() += 1;
+            }
+            assert_eq!(tree.len(), reference.values().sum::<usize>());
+            if i % 500 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+            }
+        }
+
+        let expected: Vec<u64> = reference
+            .iter()
+            .flat_map(|(&k, &count)| std::iter::repeat_n(k, count))
+            .collect();
+        assert!(tree.iter().eq(expected.iter()));
+        for (k, val) in expected.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(val));
+        }
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_overweight() {
+        let mut root = None;
+        for data in (0..4).rev() {
+            root = Some(Box::new(WBNode {
+                left: None,
+                size: WBNode::size(&root) + 1,
+                right: root,
+                data,
+            }));
+        }
+        let tree = WeightBalancedTree { root };
+
+        let err = tree.validate().unwrap_err();
+        assert_eq!(err.violation, Violation::Overweight { left: 1, right: 4 });
+        assert_eq!(err.path, "");
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree: WeightBalancedTree<i32> = [2, 1, 3].into_iter().collect();
+        let mut seen = Vec::new();
+        tree.preorder_traversal(&mut |&x| seen.push(x));
+        assert_eq!(seen, [2, 1, 3]);
+        seen.clear();
+        tree.postorder_traversal(&mut |&x| seen.push(x));
+        assert_eq!(seen, [1, 3, 2]);
+        assert!(tree.into_iter().eq([1, 2, 3]));
+    }
+
+    #[test]
+    fn test_ordered_queries_against_sorted_vec() {
+        let mut rng = Lcg(29);
+        let mut sorted: Vec<u64> = (0..2000).map(|_| rng.next() % 500).collect();
+        let mut tree: WeightBalancedTree<u64> = sorted.iter().copied().collect();
+        sorted.sort_unstable();
+
+        for _ in 0..500 {
+            let q = rng.next() % 520;
+            let d = rng.next() % 60;
+            let below = sorted.partition_point(|&x| x < q);
+            let above = sorted.partition_point(|&x| x <= q);
+            assert_eq!(
+                tree.predecessor(&q),
+                below.checked_sub(1).map(|i| &sorted[i])
+            );
+            assert_eq!(tree.successor(&q), sorted.get(above));
+            assert_eq!(tree.rank(&q), below);
+
+            let expected: Vec<u64> = sorted
+                .iter()
+                .copied()
+                .filter(|x| (q..q + d).contains(x))
+                .collect();
+            assert!(tree.range(q..q + d).copied().eq(expected.iter().copied()));
+            assert_eq!(tree.count_range(q..q + d), expected.len());
+            assert_eq!(tree.count_range(..=q), above);
+            assert_eq!(
+                tree.count_range((Bound::Excluded(q), Bound::Included(q + d))),
+                sorted.iter().filter(|&&x| x > q && x <= q + d).count()
+            );
+        }
+        assert_eq!(tree.range(10..10).count(), 0);
+        assert_eq!(
+            tree.range((Bound::Included(400), Bound::Excluded(100)))
+                .count(),
+            0
+        );
+
+        let mut reference = VecDeque::from(sorted);
+        while !reference.is_empty() {
+            if rng.next().is_multiple_of(2) {
+                assert_eq!(tree.pop_min(), reference.pop_front());
+            } else {
+                assert_eq!(tree.pop_max(), reference.pop_back());
+            }
+            if reference.len() % 100 == 0 {
+                assert_eq!(tree.validate(), Ok(()));
+                assert!(tree.iter().eq(reference.iter()));
+            }
+        }
+        assert_eq!(tree.pop_min(), None);
+        assert_eq!(tree.pop_max(), None);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let tree: WeightBalancedTree<i32> = [2, 1, 3].into_iter().collect();
+        assert_eq!(tree.to_string(), "2\n|-- 1\n`-- 3\n");
+        assert!(tree.to_dot().contains("n0 [label=\"2\"];"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let tree: WeightBalancedTree<i32> = [4, 2, 6, 2, 9].into_iter().collect();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, "[2,2,4,6,9]");
+
+        let back: WeightBalancedTree<i32> = serde_json::from_str(&json).unwrap();
+        assert!(back.iter().eq(tree.iter()));
+        assert_eq!(back.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,31 @@
+//! Data structures in Rust.
+//!
+//! The crate is organised by family of structure:
+//!
+//! - [`arrays`]: flat, contiguous containers ([`arrays::BitArray`],
+//!   [`arrays::CircularBuffer`], [`arrays::DynamicArray`], [`arrays::GapBuffer`],
+//!   [`arrays::RankSelect`]).
+//! - [`lists`]: linked lists ([`lists::DoublyLinkedList`]).
+//! - [`binary_trees`]: binary search trees ([`binary_trees::Bst`],
+//!   [`binary_trees::Avl`], [`binary_trees::BstMap`], [`binary_trees::RbTree`],
+//!   [`binary_trees::SplayTree`], [`binary_trees::Treap`],
+//!   [`binary_trees::ImplicitTreap`], [`binary_trees::ScapegoatTree`],
+//!   [`binary_trees::WeightBalancedTree`], [`binary_trees::PersistentMap`]) and
+//!   range-query trees ([`binary_trees::IntervalTree`],
+//!   [`binary_trees::SegmentTree`], [`binary_trees::FenwickTree`],
+//!   [`binary_trees::RangeFenwickTree`], [`binary_trees::FenwickTree2D`]).
+//!
+//! The most commonly used types are re-exported from [`prelude`].
+//!
+//! With the `serde` feature enabled, every container implements `Serialize`
+//! and `Deserialize` over its logical contents rather than its internal
+//! layout.
+
+pub mod arrays;
+pub mod binary_trees;
+pub mod lists;
+pub mod prelude;
+
+#[cfg(test)]
+mod test_util;
+mod util;
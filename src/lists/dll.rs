@@ -183,6 +183,30 @@ impl<T> Drop for DoublyLinkedList<T> {
     }
 }
 
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::DoublyLinkedList;
+
+    // Serialized as a sequence from front to back.
+    impl<T: Serialize> Serialize for DoublyLinkedList<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for DoublyLinkedList<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut list = Self::new();
+            for val in Vec::<T>::deserialize(deserializer)? {
+                list.push_back(val);
+            }
+            Ok(list)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,4 +329,21 @@ mod tests {
         let collected: Vec<&i32> = list.into_iter().collect();
         assert_eq!(collected, vec![&1, &2, &3]);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut list = DoublyLinkedList::new();
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(1);
+
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(json, "[1,2,3]");
+
+        let mut back: DoublyLinkedList<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 3);
+        assert_eq!(back.pop_back(), Some(3));
+        assert_eq!(back.pop_front(), Some(1));
+    }
 }
@@ -1 +1,5 @@
-mod dll;
+//! Linked lists.
+
+pub mod dll;
+
+pub use dll::DoublyLinkedList;
@@ -1,7 +1,11 @@
-mod arrays;
-mod lists;
-mod binary_trees;
+use data_structures::prelude::*;
 
 fn main() {
-    println!("Hello, world!");
+    let tree: Bst<i32> = [5, 3, 7, 2, 4, 6, 8].into_iter().collect();
+    println!("bst inorder: {:?}", tree.iter().collect::<Vec<_>>());
+
+    let mut list = DoublyLinkedList::new();
+    list.push_back("hello");
+    list.push_back("world");
+    println!("list: {:?}", list.iter().collect::<Vec<_>>());
 }
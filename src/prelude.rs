@@ -0,0 +1,16 @@
+//! Convenience re-exports of the crate's main container types.
+//!
+//! ```
+//! use data_structures::prelude::*;
+//!
+//! let mut tree = Bst::new();
+//! tree.insert(1);
+//! assert!(tree.search(1));
+//! ```
+
+pub use crate::arrays::{BitArray, CircularBuffer, DynamicArray, GapBuffer};
+pub use crate::binary_trees::{
+    Avl, Bst, BstMap, FenwickTree, ImplicitTreap, IntervalTree, PersistentMap, RbTree,
+    ScapegoatTree, SegmentTree, SplayTree, Treap, WeightBalancedTree,
+};
+pub use crate::lists::DoublyLinkedList;
@@ -0,0 +1,26 @@
+//! Helpers shared by the unit tests.
+
+/// A linear congruential generator, so randomized tests are reproducible
+/// without pulling in a dependency.
+pub(crate) struct Lcg(pub(crate) u64);
+
+impl Lcg {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    /// A random `start..end` pair with `end <= len`.
+    pub(crate) fn range(&mut self, len: usize) -> (usize, usize) {
+        let a = self.next() as usize % (len + 1);
+        let b = self.next() as usize % (len + 1);
+        (a.min(b), a.max(b))
+    }
+
+    pub(crate) fn bools(&mut self, len: usize) -> Vec<bool> {
+        (0..len).map(|_| self.next() % 2 == 1).collect()
+    }
+}
@@ -0,0 +1,25 @@
+//! Helpers shared by containers in different modules.
+
+use std::ops::{Bound, RangeBounds};
+
+/// Resolves a range of positions in a sequence of `len` items to a
+/// `start..end` pair.
+///
+/// # Panics
+///
+/// Panics if the range is reversed or runs past `len`.
+pub(crate) fn index_bounds<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range start is greater than range end");
+    assert!(end <= len, "range end out of bounds");
+    (start, end)
+}